@@ -0,0 +1,272 @@
+//! This module recovers repeating XOR keys from a known fragment of the
+//! plaintext (a crib), such as a file header or protocol banner, whose
+//! offset in the message is not known
+//!
+//! At each offset, XORing the crib with the encoded message gives the key bytes
+//! which would be needed to produce it. If the crib is longer than the key, those
+//! bytes must repeat with the key's period, which rules out most offsets and key
+//! sizes; otherwise only part of the key is recovered
+
+use std::collections::HashMap;
+
+use crypto_utilities::parallel::parallel_map;
+use single_xor_cipher_3::PlaintextScorer;
+
+/// A key implied by the crib being at one offset in the message
+#[derive(Debug, Clone, PartialEq)]
+pub struct CribKeyCandidate {
+    /// Offset of the crib in the message
+    pub offset: usize,
+    /// The key, with None for the bytes the crib does not cover
+    pub key: Vec<Option<u8>>,
+    /// The scorer's judgement of the bytes which the key decodes (lower is better)
+    pub score: f32,
+}
+
+impl CribKeyCandidate {
+    /// Returns true if the crib covered every byte of the key
+    pub fn is_complete(&self) -> bool {
+        self.key.iter().all(Option::is_some)
+    }
+
+    /// Returns the whole key, or None if part of it is not known
+    pub fn complete_key(&self) -> Option<Vec<u8>> {
+        self.key.iter().copied().collect()
+    }
+
+    /// Decodes the message as far as the key is known,
+    /// with None for the bytes under unknown key bytes
+    pub fn decode(&self, encoded_msg: &[u8]) -> Vec<Option<u8>> {
+        decode_known(encoded_msg, &self.key)
+    }
+}
+
+/// Tries the crib at every offset of the message with every key size in the range,
+/// and returns up to no_of_candidates of the keys it implies, most plausible first
+///
+/// Keys are ranked by the scorer's judgement of every byte they decode, and a
+/// key implied by the crib at several offsets is only returned once, at its best.
+/// Key sizes no longer than the crib are cheap to rule out, but every offset has
+/// to be scored for longer key sizes, so a crib longer than the key is much faster
+pub fn crack_repeating_key_xor_with_crib(
+    encoded_msg: &[u8],
+    crib: &[u8],
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_candidates: usize,
+) -> Result<Vec<CribKeyCandidate>, String> {
+    crack_repeating_key_xor_with_crib_parallel(
+        encoded_msg,
+        crib,
+        scorer,
+        min_key_size,
+        max_key_size,
+        no_of_candidates,
+        1,
+    )
+}
+
+/// Returns the same keys as crack_repeating_key_xor_with_crib,
+/// trying the offsets on up to no_of_threads threads
+///
+/// The result does not depend on the number of threads
+pub fn crack_repeating_key_xor_with_crib_parallel(
+    encoded_msg: &[u8],
+    crib: &[u8],
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_candidates: usize,
+    no_of_threads: usize,
+) -> Result<Vec<CribKeyCandidate>, String> {
+    if crib.is_empty() {
+        return Err(String::from("Cannot recover a key from an empty crib"));
+    }
+    if crib.len() > encoded_msg.len() {
+        return Err(String::from("The crib is longer than the encoded message"));
+    }
+
+    let offsets: Vec<usize> = (0..=encoded_msg.len() - crib.len()).collect();
+    let attempts = parallel_map(&offsets, no_of_threads, |&offset| {
+        get_offset_candidates(
+            encoded_msg,
+            crib,
+            offset,
+            scorer,
+            min_key_size,
+            max_key_size,
+        )
+    });
+
+    /* Reduce in offset order, so the earliest offset is kept among those giving the same key and score */
+    let mut best_candidates: HashMap<Vec<Option<u8>>, CribKeyCandidate> = HashMap::new();
+    for candidate in attempts.into_iter().flatten() {
+        let is_better = match best_candidates.get(&candidate.key) {
+            Some(best) => candidate.score < best.score,
+            None => true,
+        };
+        if is_better {
+            best_candidates.insert(candidate.key.clone(), candidate);
+        }
+    }
+
+    if best_candidates.is_empty() {
+        return Err(String::from(
+            "The crib did not imply a plausible key at any offset",
+        ));
+    }
+
+    /* Rank by score, breaking ties by offset then key size so the order is deterministic */
+    let mut candidates: Vec<CribKeyCandidate> = best_candidates.into_values().collect();
+    candidates.sort_by(|a, b| {
+        a.score
+            .total_cmp(&b.score)
+            .then(a.offset.cmp(&b.offset))
+            .then(a.key.len().cmp(&b.key.len()))
+    });
+    candidates.truncate(no_of_candidates);
+
+    Ok(candidates)
+}
+
+/// Returns the key of each size in the range which the crib implies at the offset,
+/// skipping key sizes the crib rules out and keys whose decoding the scorer rejects
+fn get_offset_candidates(
+    encoded_msg: &[u8],
+    crib: &[u8],
+    offset: usize,
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+) -> Vec<CribKeyCandidate> {
+    /* The key bytes needed to turn the encoded bytes at this offset into the crib */
+    let key_stream: Vec<u8> = encoded_msg[offset..offset + crib.len()]
+        .iter()
+        .zip(crib)
+        .map(|(encoded, plain)| encoded ^ plain)
+        .collect();
+
+    let mut candidates = Vec::new();
+    for key_size in min_key_size.max(1)..=max_key_size {
+        /* The key repeats every key_size bytes, so the key stream must too */
+        if !has_period(&key_stream, key_size) {
+            continue;
+        }
+
+        let mut key = vec![None; key_size];
+        for (idx, &key_byte) in key_stream.iter().enumerate() {
+            key[(offset + idx) % key_size] = Some(key_byte);
+        }
+
+        let decoded: Vec<u8> = decode_known(encoded_msg, &key)
+            .into_iter()
+            .flatten()
+            .collect();
+        if let Some(score) = scorer.score(&decoded) {
+            candidates.push(CribKeyCandidate { offset, key, score });
+        }
+    }
+
+    candidates
+}
+
+/// Returns true if every byte equals the byte period bytes after it
+fn has_period(bytes: &[u8], period: usize) -> bool {
+    bytes
+        .iter()
+        .zip(&bytes[period.min(bytes.len())..])
+        .all(|(a, b)| a == b)
+}
+
+/// XORs each byte of the message with the key where the key byte is known
+fn decode_known(encoded_msg: &[u8], key: &[Option<u8>]) -> Vec<Option<u8>> {
+    encoded_msg
+        .iter()
+        .zip(key.iter().cycle())
+        .map(|(encoded, key_byte)| key_byte.map(|key_byte| encoded ^ key_byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crack_base64_repeating_key_xor_with_crib;
+    use repeating_key_xor_5::multi_key_xor_encode;
+    use single_xor_cipher_3::{get_file_character_percentages, ChiSquaredScorer};
+
+    #[test]
+    fn crib_longer_than_key() {
+        let result = crack_base64_repeating_key_xor_with_crib(
+            "encoded_data.txt",
+            b"I'm back and I'm ringin' the bell",
+            "sample-text.txt",
+            2,
+            32,
+            3,
+        );
+        assert!(result.is_ok());
+
+        let candidates = result.unwrap();
+        assert_eq!(candidates[0].offset, 0);
+        assert_eq!(
+            candidates[0].complete_key(),
+            Some(b"Terminator X: Bring the noise".to_vec())
+        );
+    }
+
+    #[test]
+    fn crib_shorter_than_key() {
+        let reference_percentages = get_file_character_percentages("sample-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+
+        let msg = "Call me Ishmael. Some years ago, never mind how long precisely, having little \
+            or no money in my purse, and nothing particular to interest me on shore, I thought I \
+            would sail about a little and see the watery part of the world.";
+        let key = b"Tranquil Oyster";
+        let encoded = multi_key_xor_encode(msg.as_bytes(), key);
+
+        /* "particular" is at offset 115, so covers key bytes 10 to 14 and 0 to 4 */
+        let result = crack_repeating_key_xor_with_crib(&encoded, b"particular", &scorer, 2, 20, 5);
+        assert!(result.is_ok());
+
+        /* Every thread count should give the same ranking */
+        for no_of_threads in [2, 5] {
+            let parallel = crack_repeating_key_xor_with_crib_parallel(
+                &encoded,
+                b"particular",
+                &scorer,
+                2,
+                20,
+                5,
+                no_of_threads,
+            );
+            assert_eq!(parallel, result);
+        }
+
+        let candidates = result.unwrap();
+        assert_eq!(candidates[0].offset, 115);
+        assert!(!candidates[0].is_complete());
+        assert_eq!(candidates[0].complete_key(), None);
+
+        let expected_key: Vec<Option<u8>> = key
+            .iter()
+            .enumerate()
+            .map(|(idx, &byte)| (!(5..10).contains(&idx)).then_some(byte))
+            .collect();
+        assert_eq!(candidates[0].key, expected_key);
+
+        let decoded = candidates[0].decode(&encoded);
+        assert_eq!(decoded[0], Some(b'C'));
+        assert_eq!(decoded[5], None);
+    }
+
+    #[test]
+    fn crib_errors() {
+        let reference_percentages = get_file_character_percentages("sample-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+
+        assert!(crack_repeating_key_xor_with_crib(b"abc", b"", &scorer, 1, 4, 1).is_err());
+        assert!(crack_repeating_key_xor_with_crib(b"abc", b"abcd", &scorer, 1, 4, 1).is_err());
+    }
+}
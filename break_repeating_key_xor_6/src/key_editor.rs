@@ -0,0 +1,320 @@
+//! This module lets a repeating XOR key be corrected by hand, one byte at a time
+//!
+//! Key bytes known from elsewhere, such as a guessed word in the plaintext, are
+//! pinned, and the remaining free key bytes are cracked again by the scorer. Each
+//! edit reports which parts of the plaintext changed, so the effect of a guess
+//! can be checked before going on to the next one
+
+use std::ops::Range;
+
+use repeating_key_xor_5::multi_key_xor_encode;
+use single_xor_cipher_3::{PlaintextScorer, SingleByteXor};
+
+use crate::solution::crack_key_columns;
+
+/// The outcome of re-cracking a key with some of its bytes pinned
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyEdit {
+    /// The key after the edit
+    pub key: Vec<u8>,
+    /// The message obtained by decoding with the key
+    pub decoded: Vec<u8>,
+    /// The scorer's judgement of the decoded message (lower is better),
+    /// or None if the scorer rejects it
+    pub score: Option<f32>,
+    /// For each key byte, how much worse the second best byte for its column scored,
+    /// or None if the byte is pinned or the scorer rejected every other byte
+    pub column_confidences: Vec<Option<f32>>,
+    /// The positions of the key bytes which the edit changed
+    pub changed_columns: Vec<usize>,
+    /// The runs of message bytes which decode differently after the edit
+    pub changed_regions: Vec<Range<usize>>,
+}
+
+impl KeyEdit {
+    /// Returns the decoded message as a string, with each changed region
+    /// wrapped in the given markers
+    ///
+    /// Bytes which are not valid UTF-8 are shown as the replacement character
+    pub fn highlight_changes(&self, open: &str, close: &str) -> String {
+        let mut highlighted = String::new();
+        let mut end_of_last = 0;
+
+        for region in &self.changed_regions {
+            highlighted.push_str(&String::from_utf8_lossy(
+                &self.decoded[end_of_last..region.start],
+            ));
+            highlighted.push_str(open);
+            highlighted.push_str(&String::from_utf8_lossy(&self.decoded[region.clone()]));
+            highlighted.push_str(close);
+            end_of_last = region.end;
+        }
+        highlighted.push_str(&String::from_utf8_lossy(&self.decoded[end_of_last..]));
+
+        highlighted
+    }
+}
+
+/// Holds an encoded message and a key for it, along with
+/// the key bytes which have been pinned to a known value
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyEditor {
+    encoded_msg: Vec<u8>,
+    key: Vec<u8>,
+    pinned: Vec<Option<u8>>,
+    decoded: Vec<u8>,
+}
+
+impl KeyEditor {
+    /// Returns an editor for the message, starting from the given key with no bytes pinned
+    pub fn new(encoded_msg: &[u8], key: &[u8]) -> Result<Self, String> {
+        if key.is_empty() {
+            return Err(String::from("Cannot edit an empty key"));
+        }
+
+        Ok(Self {
+            encoded_msg: encoded_msg.to_vec(),
+            key: key.to_vec(),
+            pinned: vec![None; key.len()],
+            decoded: multi_key_xor_encode(encoded_msg, key),
+        })
+    }
+
+    /// Returns the current key
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns the pinned key bytes, with None for the free ones
+    pub fn pinned(&self) -> &[Option<u8>] {
+        &self.pinned
+    }
+
+    /// Returns the message decoded with the current key
+    pub fn decoded(&self) -> &[u8] {
+        &self.decoded
+    }
+
+    /// Pins the key byte at the given position to a value,
+    /// which takes effect at the next re-crack
+    pub fn pin(&mut self, position: usize, key_byte: u8) -> Result<(), String> {
+        match self.pinned.get_mut(position) {
+            Some(pinned) => {
+                *pinned = Some(key_byte);
+                Ok(())
+            }
+            None => Err(format!("The key only has {} bytes", self.key.len())),
+        }
+    }
+
+    /// Pins the key bytes which make the message decode to the plaintext
+    /// fragment at the given offset
+    pub fn pin_plaintext(&mut self, offset: usize, fragment: &[u8]) -> Result<(), String> {
+        if offset
+            .checked_add(fragment.len())
+            .is_none_or(|end| end > self.encoded_msg.len())
+        {
+            return Err(String::from(
+                "The fragment runs past the end of the message",
+            ));
+        }
+
+        /* Check the fragment does not need two different values for one key byte */
+        let mut pinned = self.pinned.clone();
+        let mut implied: Vec<Option<u8>> = vec![None; self.key.len()];
+        for (idx, plain) in fragment.iter().enumerate() {
+            let position = (offset + idx) % self.key.len();
+            let key_byte = self.encoded_msg[offset + idx] ^ plain;
+            if implied[position].is_some_and(|implied| implied != key_byte) {
+                return Err(format!(
+                    "The fragment needs two different values for key byte {}",
+                    position
+                ));
+            }
+            implied[position] = Some(key_byte);
+            pinned[position] = Some(key_byte);
+        }
+        self.pinned = pinned;
+
+        Ok(())
+    }
+
+    /// Frees the key byte at the given position, so the next re-crack can change it
+    pub fn unpin(&mut self, position: usize) -> Result<(), String> {
+        match self.pinned.get_mut(position) {
+            Some(pinned) => {
+                *pinned = None;
+                Ok(())
+            }
+            None => Err(format!("The key only has {} bytes", self.key.len())),
+        }
+    }
+
+    /// Sets the pinned key bytes, cracks the free ones again with the scorer,
+    /// and decodes the message with the new key
+    ///
+    /// On success, it will return the new key and decoded message, along
+    /// with which key bytes and parts of the message changed
+    pub fn recrack(&mut self, scorer: &dyn PlaintextScorer) -> Result<KeyEdit, String> {
+        self.recrack_parallel(scorer, 1)
+    }
+
+    /// Returns the same edit as recrack, cracking the free
+    /// key bytes on up to no_of_threads threads
+    pub fn recrack_parallel(
+        &mut self,
+        scorer: &dyn PlaintextScorer,
+        no_of_threads: usize,
+    ) -> Result<KeyEdit, String> {
+        let free_columns: Vec<usize> = (0..self.key.len())
+            .filter(|&position| self.pinned[position].is_none())
+            .collect();
+        let cracked = crack_key_columns(
+            &SingleByteXor,
+            &self.encoded_msg,
+            self.key.len(),
+            &free_columns,
+            scorer,
+            no_of_threads,
+        )?;
+
+        let mut key: Vec<u8> = self
+            .pinned
+            .iter()
+            .zip(&self.key)
+            .map(|(pinned, key_byte)| pinned.unwrap_or(*key_byte))
+            .collect();
+        let mut column_confidences = vec![None; key.len()];
+        for (&position, (key_byte, score_gap)) in free_columns.iter().zip(cracked) {
+            key[position] = key_byte;
+            column_confidences[position] = score_gap;
+        }
+
+        let decoded = multi_key_xor_encode(&self.encoded_msg, &key);
+        let changed_columns = (0..key.len())
+            .filter(|&position| key[position] != self.key[position])
+            .collect();
+        let changed_regions = get_changed_regions(&self.decoded, &decoded);
+        let score = scorer.score(&decoded);
+
+        self.key = key.clone();
+        self.decoded = decoded.clone();
+
+        Ok(KeyEdit {
+            key,
+            decoded,
+            score,
+            column_confidences,
+            changed_columns,
+            changed_regions,
+        })
+    }
+}
+
+/// Returns the runs of positions at which the two equal length buffers differ
+fn get_changed_regions(before: &[u8], after: &[u8]) -> Vec<Range<usize>> {
+    let mut regions: Vec<Range<usize>> = Vec::new();
+
+    for (idx, (a, b)) in before.iter().zip(after).enumerate() {
+        if a == b {
+            continue;
+        }
+
+        /* Extend the last region if this byte follows on from it */
+        match regions.last_mut() {
+            Some(region) if region.end == idx => region.end += 1,
+            _ => regions.push(idx..idx + 1),
+        }
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use single_xor_cipher_3::{get_file_character_percentages, ChiSquaredScorer};
+
+    const MSG: &str = "When in the Course of human events, it becomes necessary for one people \
+        to dissolve the political bands which have connected them with another, and to assume \
+        among the powers of the earth, the separate and equal station to which the Laws of \
+        Nature and of Nature's God entitle them, a decent respect to the opinions of mankind \
+        requires that they should declare the causes which impel them to the separation.";
+
+    #[test]
+    fn pinned_bytes_are_kept() {
+        let reference_percentages = get_file_character_percentages("sample-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+        let key = b"Oyster";
+        let encoded = multi_key_xor_encode(MSG.as_bytes(), key);
+
+        /* Start from a key with two wrong bytes */
+        let mut editor = KeyEditor::new(&encoded, b"OXstZr").unwrap();
+        assert_ne!(editor.decoded(), MSG.as_bytes());
+
+        /* Pin the key bytes under a guessed word, and let the scorer fix the rest */
+        editor.pin_plaintext(8, b"the").unwrap();
+        assert_eq!(
+            editor.pinned(),
+            [None, None, Some(b's'), Some(b't'), Some(b'e'), None]
+        );
+
+        let edit = editor.recrack(&scorer).unwrap();
+        assert_eq!(edit.key, key);
+        assert_eq!(edit.decoded, MSG.as_bytes());
+        assert_eq!(edit.changed_columns, vec![1, 4]);
+        assert!(edit.score.is_some());
+        assert!(edit.column_confidences[1].is_some());
+        assert_eq!(edit.column_confidences[4], None);
+        assert_eq!(editor.key(), key);
+
+        /* Every changed byte sits under one of the two changed key bytes */
+        assert_eq!(edit.changed_regions[0], 1..2);
+        assert_eq!(edit.changed_regions[1], 4..5);
+        assert!(edit
+            .changed_regions
+            .iter()
+            .flat_map(|region| region.clone())
+            .all(|idx| idx % 6 == 1 || idx % 6 == 4));
+        assert!(edit
+            .highlight_changes("[", "]")
+            .starts_with("W[h]en[ ]in[ ]th[e] "));
+
+        /* A pinned byte overrides the scorer, even when it is wrong */
+        editor.pin(0, b'X').unwrap();
+        let edit = editor.recrack(&scorer).unwrap();
+        assert_eq!(edit.key, b"Xyster");
+        assert_eq!(edit.changed_columns, vec![0]);
+
+        /* Freeing it lets the scorer put it right again */
+        editor.unpin(0).unwrap();
+        let edit = editor.recrack(&scorer).unwrap();
+        assert_eq!(edit.key, key);
+        assert_eq!(edit.highlight_changes("<", ">"), {
+            let mut expected = String::new();
+            for (idx, c) in MSG.chars().enumerate() {
+                if idx % 6 == 0 {
+                    expected.push_str(&format!("<{}>", c));
+                } else {
+                    expected.push(c);
+                }
+            }
+            expected
+        });
+    }
+
+    #[test]
+    fn key_editor_errors() {
+        assert!(KeyEditor::new(b"abc", b"").is_err());
+
+        let mut editor = KeyEditor::new(b"abcdef", b"xy").unwrap();
+        assert!(editor.pin(2, 0).is_err());
+        assert!(editor.unpin(2).is_err());
+        assert!(editor.pin_plaintext(4, b"abc").is_err());
+        assert!(editor.pin_plaintext(usize::MAX, b"abc").is_err());
+
+        /* Bytes 0 and 2 share a key byte, but would need different values for it */
+        assert!(editor.pin_plaintext(0, b"aaa").is_err());
+        assert_eq!(editor.pinned(), [None, None]);
+    }
+}
@@ -0,0 +1,348 @@
+//! This module provides several ways of estimating the size of a repeating
+//! XOR key, which can be used alone or combined
+//!
+//! Every estimator gives a score to each candidate key size, where lower is more
+//! likely, following the chi-squared convention used by the rest of the crackers.
+//! Scores from different estimators are on different scales, so they are
+//! standardised before being combined
+
+use std::{
+    cmp::{max, min},
+    collections::HashMap,
+};
+
+use crypto_utilities::analysis::get_index_of_coincidence;
+
+use crate::get_hamming_distance;
+
+/// Index of coincidence of English text as bytes, including spaces and punctuation
+const PLAINTEXT_INDEX_OF_COINCIDENCE: f64 = 0.0667;
+
+/// Index of coincidence of uniformly random bytes
+const RANDOM_INDEX_OF_COINCIDENCE: f64 = 1.0 / 256.0;
+
+/// Length of the repeated sequences the Kasiski examination looks for
+const KASISKI_SEQUENCE_LEN: usize = 3;
+
+/// Most blocks compared by the all-pairs Hamming estimator, which
+/// bounds its cost at the square of this number of comparisons
+const ALL_PAIRS_MAX_BLOCKS: usize = 64;
+
+/// A combination of estimators which works well for English plaintext.
+/// The Kasiski examination picks the key size out from its multiples, which
+/// the index of coincidence and Hamming distances cannot tell apart
+pub const DEFAULT_KEY_SIZE_ESTIMATORS: [(KeySizeEstimator, f64); 3] = [
+    (KeySizeEstimator::ColumnIndexOfCoincidence, 1.0),
+    (KeySizeEstimator::AllPairsHamming, 1.0),
+    (KeySizeEstimator::Kasiski, 1.0),
+];
+
+/// A way of scoring candidate key sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeySizeEstimator {
+    /// Average Hamming distance per bit between consecutive blocks of key size bytes
+    ConsecutiveHamming,
+    /// Average Hamming distance per bit between every pair of the first blocks of key size bytes
+    AllPairsHamming,
+    /// Average index of coincidence of the columns of bytes encoded with the same key byte
+    ColumnIndexOfCoincidence,
+    /// Distance from the key size estimated by the Friedman test. This is only a
+    /// rough guide, since XOR keys made of similar bytes leave the message less random
+    Friedman,
+    /// How much more often than chance the key size divides the spacing of repeated sequences
+    Kasiski,
+}
+
+impl KeySizeEstimator {
+    /// Every estimator
+    pub const ALL: [KeySizeEstimator; 5] = [
+        KeySizeEstimator::ConsecutiveHamming,
+        KeySizeEstimator::AllPairsHamming,
+        KeySizeEstimator::ColumnIndexOfCoincidence,
+        KeySizeEstimator::Friedman,
+        KeySizeEstimator::Kasiski,
+    ];
+}
+
+/// The score an estimator gave to a candidate key size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeySizeScore {
+    /// The candidate key size
+    pub key_size: usize,
+    /// How likely the key size is (lower is better)
+    pub score: f64,
+}
+
+/// Scores every key size in the range with the estimator, in key size order
+///
+/// Key sizes larger than half of the message are not scored, as at least
+/// two blocks are needed to compare
+pub fn get_key_size_scores(
+    encoded_msg: &[u8],
+    estimator: KeySizeEstimator,
+    min_key_size: usize,
+    max_key_size: usize,
+) -> Vec<KeySizeScore> {
+    let key_sizes = max(1, min_key_size)..=min(max_key_size, encoded_msg.len() / 2);
+
+    /* These estimators look at the whole message once, rather than once per key size */
+    let friedman_estimate = match estimator {
+        KeySizeEstimator::Friedman => Some(get_friedman_estimate(encoded_msg)),
+        _ => None,
+    };
+    let kasiski_spacings = match estimator {
+        KeySizeEstimator::Kasiski => get_kasiski_spacings(encoded_msg),
+        _ => Vec::new(),
+    };
+
+    key_sizes
+        .map(|key_size| {
+            let score = match estimator {
+                KeySizeEstimator::ConsecutiveHamming => {
+                    get_consecutive_hamming(encoded_msg, key_size)
+                }
+                KeySizeEstimator::AllPairsHamming => get_all_pairs_hamming(encoded_msg, key_size),
+                KeySizeEstimator::ColumnIndexOfCoincidence => {
+                    -get_column_index_of_coincidence(encoded_msg, key_size)
+                }
+                KeySizeEstimator::Friedman => (key_size as f64 - friedman_estimate.unwrap()).abs(),
+                KeySizeEstimator::Kasiski => get_kasiski_score(&kasiski_spacings, key_size),
+            };
+
+            KeySizeScore { key_size, score }
+        })
+        .collect()
+}
+
+/// Scores every key size in the range with each weighted estimator, in key size order
+///
+/// Each estimator's scores are standardised to a mean of zero and a standard
+/// deviation of one over the range before being weighted and summed, so no
+/// estimator dominates just because of the scale of its scores
+pub fn get_combined_key_size_scores(
+    encoded_msg: &[u8],
+    estimators: &[(KeySizeEstimator, f64)],
+    min_key_size: usize,
+    max_key_size: usize,
+) -> Vec<KeySizeScore> {
+    let mut combined: Vec<KeySizeScore> = Vec::new();
+
+    for &(estimator, weight) in estimators {
+        let scores = get_key_size_scores(encoded_msg, estimator, min_key_size, max_key_size);
+        if combined.is_empty() {
+            combined = scores
+                .iter()
+                .map(|score| KeySizeScore {
+                    key_size: score.key_size,
+                    score: 0.0,
+                })
+                .collect();
+        }
+
+        let len = scores.len() as f64;
+        let mean = scores.iter().map(|score| score.score).sum::<f64>() / len;
+        let variance = scores
+            .iter()
+            .map(|score| (score.score - mean) * (score.score - mean))
+            .sum::<f64>()
+            / len;
+
+        /* An estimator which scores every size the same cannot tell them apart */
+        if variance == 0.0 {
+            continue;
+        }
+
+        let std_dev = variance.sqrt();
+        for (total, score) in combined.iter_mut().zip(&scores) {
+            total.score += weight * (score.score - mean) / std_dev;
+        }
+    }
+
+    combined
+}
+
+/// Returns up to no_of_sizes key sizes in the range, most likely first,
+/// according to the combination of weighted estimators
+///
+/// Ties are broken by the smaller key size, since multiples of the
+/// key size often look as likely as the key size itself
+pub fn get_likely_key_sizes_with_estimators(
+    encoded_msg: &[u8],
+    estimators: &[(KeySizeEstimator, f64)],
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Vec<usize> {
+    let mut scores =
+        get_combined_key_size_scores(encoded_msg, estimators, min_key_size, max_key_size);
+
+    /* The scores are in key size order, so a stable sort breaks ties by the smaller size */
+    scores.sort_by(|a, b| a.score.total_cmp(&b.score));
+
+    scores
+        .into_iter()
+        .take(no_of_sizes)
+        .map(|score| score.key_size)
+        .collect()
+}
+
+/// Returns the average Hamming distance per bit between consecutive blocks of key_size bytes
+fn get_consecutive_hamming(encoded_msg: &[u8], key_size: usize) -> f64 {
+    let blocks: Vec<&[u8]> = encoded_msg.chunks_exact(key_size).collect();
+
+    let total: usize = blocks
+        .windows(2)
+        .map(|pair| get_hamming_distance(pair[0], pair[1]).unwrap())
+        .sum();
+
+    total as f64 / ((blocks.len() - 1) * key_size * 8) as f64
+}
+
+/// Returns the average Hamming distance per bit between every pair of the
+/// first ALL_PAIRS_MAX_BLOCKS blocks of key_size bytes
+fn get_all_pairs_hamming(encoded_msg: &[u8], key_size: usize) -> f64 {
+    let blocks: Vec<&[u8]> = encoded_msg
+        .chunks_exact(key_size)
+        .take(ALL_PAIRS_MAX_BLOCKS)
+        .collect();
+
+    let mut total = 0;
+    let mut no_of_pairs = 0;
+    for (idx, first) in blocks.iter().enumerate() {
+        for second in &blocks[idx + 1..] {
+            total += get_hamming_distance(first, second).unwrap();
+            no_of_pairs += 1;
+        }
+    }
+
+    total as f64 / (no_of_pairs * key_size * 8) as f64
+}
+
+/// Returns the average index of coincidence of the key_size columns of the message
+fn get_column_index_of_coincidence(encoded_msg: &[u8], key_size: usize) -> f64 {
+    let total: f64 = (0..key_size)
+        .map(|column| {
+            let column: Vec<u8> = encoded_msg
+                .iter()
+                .skip(column)
+                .step_by(key_size)
+                .copied()
+                .collect();
+            get_index_of_coincidence(&column)
+        })
+        .sum();
+
+    total / key_size as f64
+}
+
+/// Estimates the key size from the index of coincidence of the whole message
+/// using the Friedman test, assuming the plaintext is English
+fn get_friedman_estimate(encoded_msg: &[u8]) -> f64 {
+    let index_of_coincidence = get_index_of_coincidence(encoded_msg);
+
+    /* The closer the message is to random, the longer the key must be */
+    (PLAINTEXT_INDEX_OF_COINCIDENCE - RANDOM_INDEX_OF_COINCIDENCE)
+        / (index_of_coincidence - RANDOM_INDEX_OF_COINCIDENCE).max(f64::EPSILON)
+}
+
+/// Returns the distances between consecutive occurrences of every
+/// sequence of KASISKI_SEQUENCE_LEN bytes which appears more than once
+fn get_kasiski_spacings(encoded_msg: &[u8]) -> Vec<usize> {
+    let mut last_seen: HashMap<&[u8], usize> = HashMap::new();
+    let mut spacings = Vec::new();
+
+    for (idx, sequence) in encoded_msg.windows(KASISKI_SEQUENCE_LEN).enumerate() {
+        if let Some(last_idx) = last_seen.insert(sequence, idx) {
+            spacings.push(idx - last_idx);
+        }
+    }
+
+    spacings
+}
+
+/// Scores a key size by how much more often than chance it divides the spacings
+///
+/// A key size divides a random spacing with a chance of 1 / key_size. Multiples of
+/// the key size divide fewer spacings, and its factors are more likely by chance,
+/// so the key size itself stands out the most
+fn get_kasiski_score(spacings: &[usize], key_size: usize) -> f64 {
+    if spacings.is_empty() {
+        return 0.0;
+    }
+
+    let divisible = spacings
+        .iter()
+        .filter(|&&spacing| spacing.is_multiple_of(key_size))
+        .count();
+
+    1.0 / key_size as f64 - divisible as f64 / spacings.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_base64_file;
+    use repeating_key_xor_5::multi_key_xor_encode;
+    use std::fs;
+
+    /// Returns the key size which the estimator alone finds most likely
+    fn best_key_size(encoded_msg: &[u8], estimator: KeySizeEstimator) -> usize {
+        get_likely_key_sizes_with_estimators(encoded_msg, &[(estimator, 1.0)], 2, 40, 1)[0]
+    }
+
+    #[test]
+    fn estimators_find_key_size() {
+        let encoded = read_base64_file("encoded_data.txt").unwrap();
+
+        /* The key is 29 bytes, which has no multiples or factors in the range */
+        for estimator in [
+            KeySizeEstimator::ConsecutiveHamming,
+            KeySizeEstimator::AllPairsHamming,
+            KeySizeEstimator::ColumnIndexOfCoincidence,
+            KeySizeEstimator::Kasiski,
+        ] {
+            assert_eq!(best_key_size(&encoded, estimator), 29);
+        }
+
+        let result =
+            get_likely_key_sizes_with_estimators(&encoded, &DEFAULT_KEY_SIZE_ESTIMATORS, 2, 40, 3);
+        assert_eq!(result[0], 29);
+    }
+
+    #[test]
+    fn combined_estimators_prefer_key_size_over_multiples() {
+        let plain = fs::read("long-text.txt").unwrap();
+        let encoded = multi_key_xor_encode(&plain[..3000], b"Lantern");
+
+        /* Columns of a multiple of the key size are just as uniform as those of the key size */
+        assert_eq!(
+            best_key_size(&encoded, KeySizeEstimator::ColumnIndexOfCoincidence) % 7,
+            0
+        );
+        assert_eq!(best_key_size(&encoded, KeySizeEstimator::Kasiski), 7);
+
+        let result =
+            get_likely_key_sizes_with_estimators(&encoded, &DEFAULT_KEY_SIZE_ESTIMATORS, 2, 40, 3);
+        assert_eq!(result[0], 7);
+    }
+
+    #[test]
+    fn key_size_scores_cover_range() {
+        let encoded = multi_key_xor_encode(b"abcdefghijklmnopqrst", b"key");
+
+        /* Key sizes beyond half the message are left out */
+        for estimator in KeySizeEstimator::ALL {
+            let scores = get_key_size_scores(&encoded, estimator, 0, 40);
+            let key_sizes: Vec<usize> = scores.iter().map(|score| score.key_size).collect();
+            assert_eq!(key_sizes, (1..=10).collect::<Vec<usize>>());
+        }
+
+        /* No sequence repeats, so the Kasiski examination has nothing to go on */
+        let scores = get_key_size_scores(&encoded, KeySizeEstimator::Kasiski, 1, 10);
+        assert!(scores.iter().all(|score| score.score == 0.0));
+
+        let combined =
+            get_combined_key_size_scores(&encoded, &[(KeySizeEstimator::Kasiski, 1.0)], 1, 10);
+        assert!(combined.iter().all(|score| score.score == 0.0));
+    }
+}
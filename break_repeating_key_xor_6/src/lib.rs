@@ -1,22 +1,53 @@
 //! This crate breaks the base64, XOR encoded data in encoded_data.txt
 
 use std::{
-    cmp::{max, min},
-    collections::{BinaryHeap, HashMap},
-    fs::File,
+    collections::HashMap,
+    fs::{self, File},
     io::Read,
     iter::zip,
     str,
 };
 
-use repeating_key_xor_5::multi_key_xor_encode;
+pub mod crib;
+pub mod key_editor;
+pub mod key_size;
+pub mod period;
+pub mod solution;
+pub mod vigenere;
+pub mod wordlist;
+
+pub use crib::{
+    crack_repeating_key_xor_with_crib, crack_repeating_key_xor_with_crib_parallel, CribKeyCandidate,
+};
+pub use crypto_utilities::{base64_to_binary_buf, char_to_base64_value};
+pub use key_editor::{KeyEdit, KeyEditor};
+pub use key_size::{
+    get_combined_key_size_scores, get_key_size_scores, get_likely_key_sizes_with_estimators,
+    KeySizeEstimator, KeySizeScore, DEFAULT_KEY_SIZE_ESTIMATORS,
+};
+pub use period::{
+    get_candidate_periods, get_period_agreement, reduce_to_fundamental_period,
+    reduce_to_fundamental_period_parallel, MIN_PERIOD_AGREEMENT,
+};
 use single_xor_cipher_3::{
-    get_character_percentages, get_chi_squared, get_file_character_percentages,
-    single_xor_cipher_crack,
+    get_file_character_percentages, ChiSquaredScorer, PlaintextScorer, SingleByteXor,
+};
+use solution::crack_sized_key_columns;
+pub use solution::{
+    crack_repeating_key_xor_solutions, crack_repeating_key_xor_solutions_parallel,
+    PeriodicSolution, RepeatingXorSolution,
+};
+pub use vigenere::{
+    crack_polyalphabetic, crack_polyalphabetic_parallel, polyalphabetic_decrypt,
+    polyalphabetic_encrypt, PolyalphabeticCipher, PolyalphabeticSolution,
+};
+pub use wordlist::{
+    crack_repeating_key_xor_with_wordlist, crack_repeating_key_xor_with_wordlist_parallel,
+    get_key_mutations, read_wordlist, WordlistCandidate, WordlistMutations,
 };
 
 /// This function computes the Hamming distance between two u8 buffers
-pub fn get_hamming_distance(buf1: &[u8], buf2: &[u8]) -> Result<i32, String> {
+pub fn get_hamming_distance(buf1: &[u8], buf2: &[u8]) -> Result<usize, String> {
     /*  The Hamming distance can only be calculated between two equal length buffers */
     if buf1.len() != buf2.len() {
         return Err(String::from(
@@ -44,67 +75,21 @@ pub fn get_hamming_distance(buf1: &[u8], buf2: &[u8]) -> Result<i32, String> {
 /// This function gets the likely key sizes based
 /// off the average Hamming distance between consecutive
 /// blocks of "keysize" bytes in the encoded message
+///
+/// The key sizes are returned most likely first
 pub fn get_likely_key_sizes(
     encoded_msg: &[u8],
     min_key_size: usize,
     max_key_size: usize,
     no_of_sizes: usize,
-) -> Vec<u8> {
-    /* If no likely keysizes were requested, return */
-    if no_of_sizes == 0 {
-        return Vec::new();
-    }
-
-    /*
-     * Binary heap of key sizes, and associated average Hamming
-     * distances between consecutive blocks of key size bytes
-     *
-     * The first element of the tuple will be the Hamming
-     * distance as that is what determines the ordering
-     * in (i32,u8)'s default implementation of Ord
-     */
-    let mut keysizes = BinaryHeap::<(i32, u8)>::new();
-
-    /* Iterate through the range of key sizes given */
-    for key_size in max(1, min_key_size)..=min(max_key_size, encoded_msg.len() / 2) {
-        /*
-         * Find the total Hamming distances between consecutive blocks
-         * of key_size bytes (this should be an indicator of how likely it
-         * is that the given key_size is correct)
-         */
-        let mut start = 0;
-        let mut middle = key_size;
-        let mut end = 2 * key_size;
-        let mut total_hamming = 0;
-        while end <= encoded_msg.len() {
-            total_hamming +=
-                get_hamming_distance(&encoded_msg[start..middle], &encoded_msg[middle..end])
-                    .unwrap();
-            start += key_size;
-            middle += key_size;
-            end += key_size;
-        }
-
-        /* Obtain the average Hamming distance between consecutive blocks */
-        let average_hamming = total_hamming / (encoded_msg.len() / key_size) as i32;
-
-        /*
-         * Insert the normalised Hamming distance and keysize pair into the
-         * binary heap, sorted by Hamming distance (in ascending order)
-         *
-         * The average Hamming distance was multiplied by 1000 before normalising
-         * for greater granularity
-         */
-        keysizes.push((average_hamming * 1000 / key_size as i32, key_size as u8));
-
-        /* Pop the pair with the largest Hamming distance if necessary */
-        if keysizes.len() > no_of_sizes {
-            keysizes.pop();
-        }
-    }
-
-    /* Return the most likely key sizes */
-    keysizes.into_iter().map(|(_, key_size)| key_size).collect()
+) -> Vec<usize> {
+    get_likely_key_sizes_with_estimators(
+        encoded_msg,
+        &[(KeySizeEstimator::ConsecutiveHamming, 1.0)],
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+    )
 }
 
 /// Given the encoded message and key size, returns the key
@@ -116,122 +101,104 @@ pub fn get_sized_key(
     key_size: usize,
     reference_percentages: &HashMap<char, f32>,
 ) -> Result<Vec<u8>, String> {
-    let mut key = Vec::new();
-
-    /*
-     * Since we are doing multi-byte XORing, we can use
-     * crack the key a byte at a time, by collecting all
-     * the bytes which that byte of the key will apply to,
-     * then acting as if it was a single byte XOR cipher
-     * */
-    for key_byte_no in 0..key_size {
-        let mut current_encoded_bytes = Vec::new();
-        for (idx, byte) in encoded_msg.iter().enumerate() {
-            if idx % key_size == key_byte_no {
-                current_encoded_bytes.push(*byte);
-            }
-        }
-
-        key.push(single_xor_cipher_crack(&current_encoded_bytes, reference_percentages)?.0);
-        current_encoded_bytes.clear();
-    }
-
-    Ok(key)
+    get_sized_key_with_scorer(
+        encoded_msg,
+        key_size,
+        &ChiSquaredScorer::new(reference_percentages),
+    )
 }
 
-/// Converts a base64 character into its base64 numeric value
-pub fn char_to_base64_value(c: char) -> Result<Option<u8>, String> {
-    match c {
-        'A'..='Z' => Ok(Some((c as u32 - 'A' as u32) as u8)),
-        'a'..='z' => Ok(Some((c as u32 - 'a' as u32 + 26) as u8)),
-        '0'..='9' => Ok(Some((c as u32 - '0' as u32 + 52) as u8)),
-        '+' => Ok(Some(62)),
-        '/' => Ok(Some(63)),
-        '=' => Ok(None),
-        _ => Err(String::from("Non base64 character passed")),
-    }
+/// Given the encoded message and key size, returns the key which
+/// when XORed with the encoded message, results in the decoded
+/// bytes the scorer finds most plausible, a column at a time
+pub fn get_sized_key_with_scorer(
+    encoded_msg: &[u8],
+    key_size: usize,
+    scorer: &dyn PlaintextScorer,
+) -> Result<Vec<u8>, String> {
+    get_sized_key_parallel(encoded_msg, key_size, scorer, 1)
 }
 
-/// Decodes a base64 string into the corresponding binary buffer
-pub fn base64_to_binary_buf(base64: &str) -> Result<Vec<u8>, String> {
-    let mut buffer = Vec::new();
-
-    /* Perform the marshaling 24 bits at a time, as base64 operates on groups of 24 bits */
-    let mut temp: [u8; 3] = [0; 3];
-
-    for (idx, base64_char) in base64.chars().enumerate() {
-        if let Some(base64_value) = char_to_base64_value(base64_char)? {
-            match idx % 4 {
-                0 => temp[0] = base64_value << 2,
-                1 => {
-                    temp[0] |= base64_value >> 4;
-                    temp[1] = base64_value << 4;
-                }
-                2 => {
-                    temp[1] |= base64_value >> 2;
-                    temp[2] = base64_value << 6;
-                }
-                _ => {
-                    temp[2] |= base64_value;
-                    /* Push bytes into buffer once 24 bits have been processed */
-                    for byte in temp {
-                        buffer.push(byte);
-                    }
-                    /* Reinitialise to zero for next iteration */
-                    temp = [0; 3];
-                }
-            }
-        } else {
-            /* If padding is encountered, push remaining bytes which contain data */
-            match idx % 4 {
-                0 | 1 => return Err("Invalid padding position".to_string()),
-                2 => {
-                    buffer.push(temp[0]);
-                }
-                _ => {
-                    buffer.extend_from_slice(&temp[..2]);
-                }
-            }
-            break;
-        }
-    }
+/// Returns the same key as get_sized_key_with_scorer, cracking
+/// the columns of the key on up to no_of_threads threads
+pub fn get_sized_key_parallel(
+    encoded_msg: &[u8],
+    key_size: usize,
+    scorer: &dyn PlaintextScorer,
+    no_of_threads: usize,
+) -> Result<Vec<u8>, String> {
+    let (key, _) =
+        crack_sized_key_columns(&SingleByteXor, encoded_msg, key_size, scorer, no_of_threads)?;
 
-    Ok(buffer)
+    Ok(key)
 }
 
 /// Crack the base64 encoded, XOR encoded data by
 /// using Hamming distance to obtain guesses for the key size,
 /// then crack the key a byte at a time, acting as if it was a series
 /// of single byte XOR ciphers
+///
+/// On success, it will return the solution for each likely key size,
+/// ranked by the character frequencies of the messages they decode
 pub fn crack_base64_repeating_key_xor(
     encoded_msg_file: &str,
     reference_file: &str,
     min_key_size: usize,
     max_key_size: usize,
     no_of_sizes: usize,
-) -> Result<String, String> {
-    /* Get the encoded message into a String */
-    let mut file = match File::open(encoded_msg_file) {
-        Ok(file) => file,
-        Err(e) => return Err(e.to_string()),
-    };
-    let mut file_text = String::new();
-    if let Err(e) = file.read_to_string(&mut file_text) {
-        return Err(e.to_string());
-    };
+) -> Result<Vec<RepeatingXorSolution>, String> {
+    crack_base64_repeating_key_xor_parallel(
+        encoded_msg_file,
+        reference_file,
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+        1,
+    )
+}
 
-    /* Newlines are not base64, so remove them */
-    file_text.retain(|c| !c.is_whitespace());
+/// Recover the key of the base64 encoded, XOR encoded data from a crib,
+/// a fragment of the plaintext whose offset is not known
+///
+/// On success, it will return up to no_of_candidates complete or partial keys,
+/// ranked by the character frequencies of the bytes they decode
+pub fn crack_base64_repeating_key_xor_with_crib(
+    encoded_msg_file: &str,
+    crib: &[u8],
+    reference_file: &str,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_candidates: usize,
+) -> Result<Vec<CribKeyCandidate>, String> {
+    let buffer = read_base64_file(encoded_msg_file)?;
 
-    /* Marshal the base64 string into a binary buffer */
-    let buffer = base64_to_binary_buf(&file_text)?;
+    /* Get character frequencies of reference file */
+    let reference_percentages = match get_file_character_percentages(reference_file) {
+        Ok(reference_percentages) => reference_percentages,
+        Err(e) => return Err(e.to_string()),
+    };
 
-    /* Use the Hamming distances to get the likely key sizes */
-    let likely_key_sizes = get_likely_key_sizes(&buffer, min_key_size, max_key_size, no_of_sizes);
+    crack_repeating_key_xor_with_crib(
+        &buffer,
+        crib,
+        &ChiSquaredScorer::new(&reference_percentages),
+        min_key_size,
+        max_key_size,
+        no_of_candidates,
+    )
+}
 
-    /* Keep track of which key sizes resulted in the most plausible character frequencies in the decoded message */
-    let mut best_chi_squared = None;
-    let mut best_decoded = None;
+/// Returns the same solutions as crack_base64_repeating_key_xor,
+/// evaluating the key sizes and cracking the key on up to no_of_threads threads
+pub fn crack_base64_repeating_key_xor_parallel(
+    encoded_msg_file: &str,
+    reference_file: &str,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+    no_of_threads: usize,
+) -> Result<Vec<RepeatingXorSolution>, String> {
+    let buffer = read_base64_file(encoded_msg_file)?;
 
     /* Get character frequencies of reference file */
     let reference_percentages = match get_file_character_percentages(reference_file) {
@@ -239,44 +206,225 @@ pub fn crack_base64_repeating_key_xor(
         Err(e) => return Err(e.to_string()),
     };
 
-    /* For each key size, get the most likely key */
-    for key_size in likely_key_sizes {
-        /* If a key could not be obtained, move onto the next key size */
-        let key = match get_sized_key(&buffer, key_size as usize, &reference_percentages) {
-            Ok(key) => key,
-            Err(_) => continue,
-        };
+    crack_repeating_key_xor_solutions_parallel(
+        &buffer,
+        &ChiSquaredScorer::new(&reference_percentages),
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+        no_of_threads,
+    )
+}
+
+/// Crack the base64 encoded, XOR encoded data under each of the
+/// named language profiles given
+///
+/// On success, it will return the most plausible decoded message,
+/// along with the name of the language it was found in
+pub fn crack_base64_repeating_key_xor_languages<'a>(
+    encoded_msg_file: &str,
+    language_percentages: &[(&'a str, &HashMap<char, f32>)],
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Result<(String, &'a str), String> {
+    let buffer = read_base64_file(encoded_msg_file)?;
+
+    crack_repeating_key_xor_languages(
+        &buffer,
+        language_percentages,
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+    )
+}
 
-        /* Decode the message using the key */
-        let decoded = multi_key_xor_encode(&buffer, &key);
+/// Crack the XOR encoded buffer under each of the named language profiles given
+///
+/// On success, it will return the most plausible decoded message,
+/// along with the name of the language it was found in
+pub fn crack_repeating_key_xor_languages<'a>(
+    encoded_msg: &[u8],
+    language_percentages: &[(&'a str, &HashMap<char, f32>)],
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Result<(String, &'a str), String> {
+    let mut best_chi_squared = None;
+    let mut best_decoded = None;
 
-        /* If the result is not a valid UTF-8 string, continue to the next key size */
-        let decoded_str = match str::from_utf8(&decoded) {
-            Ok(decoded_str) => decoded_str,
+    for &(language, reference_percentages) in language_percentages {
+        /* If the message cannot be decoded under this language, try the next one */
+        let (decoded, chi_squared) = match crack_repeating_key_xor(
+            encoded_msg,
+            reference_percentages,
+            min_key_size,
+            max_key_size,
+            no_of_sizes,
+        ) {
+            Ok(result) => result,
             Err(_) => continue,
         };
 
-        /* Get metric on how plausible the character frequencies in the decoded message are */
-        let decoded_percentages = get_character_percentages(decoded_str);
-        let chi_squared = get_chi_squared(&reference_percentages, decoded_percentages);
-
-        /* If this key results in more plausible character frequencies, record it */
+        /* If this language results in more plausible character frequencies, record it */
         if best_chi_squared.is_none() || chi_squared < best_chi_squared.unwrap() {
             best_chi_squared = Some(chi_squared);
-            best_decoded = Some(String::from(decoded_str));
+            best_decoded = Some((decoded, language));
         }
     }
 
-    /* If a decoded message was obtained, return it */
     match best_decoded {
         Some(ret) => Ok(ret),
-        _ => Err(String::from("Could not decode the given base64 file.")),
+        _ => Err(String::from(
+            "Could not decode the given buffer under any language.",
+        )),
     }
 }
 
+/// Crack the XOR encoded buffer by using Hamming distance to obtain
+/// guesses for the key size, then crack the key a byte at a time
+///
+/// On success, it will return the decoded message and the chi-squared
+/// metric between its character frequencies and the reference frequencies
+pub fn crack_repeating_key_xor(
+    encoded_msg: &[u8],
+    reference_percentages: &HashMap<char, f32>,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Result<(String, f32), String> {
+    let (decoded, chi_squared) = crack_repeating_key_xor_with_scorer(
+        encoded_msg,
+        &ChiSquaredScorer::new(reference_percentages),
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+    )?;
+
+    /* The chi-squared scorer only accepts decoded messages which are valid UTF-8 */
+    Ok((String::from_utf8(decoded).unwrap(), chi_squared))
+}
+
+/// Crack the base64 encoded, XOR encoded data in the given file,
+/// judging candidate decodings with the given scorer
+///
+/// On success, it will return the decoded bytes
+pub fn crack_base64_repeating_key_xor_with_scorer(
+    encoded_msg_file: &str,
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Result<Vec<u8>, String> {
+    let buffer = read_base64_file(encoded_msg_file)?;
+
+    let (decoded, _) = crack_repeating_key_xor_with_scorer(
+        &buffer,
+        scorer,
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+    )?;
+
+    Ok(decoded)
+}
+
+/// Crack the XOR encoded contents of the given file, taken as raw bytes,
+/// judging candidate decodings with the given scorer
+///
+/// This is a convenience which reads the file and passes its bytes to
+/// crack_repeating_key_xor_solutions, which takes ciphertext already in memory
+/// as a raw &[u8]. Nothing is assumed about the encoding of the plaintext, so
+/// a byte-level scorer such as an NgramModel or FileSignatureScorer can recover
+/// documents in any text encoding, as well as binary files
+///
+/// On success, it will return the solution for each likely key size, most plausible first
+pub fn crack_raw_repeating_key_xor(
+    encoded_msg_file: &str,
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Result<Vec<RepeatingXorSolution>, String> {
+    let buffer = fs::read(encoded_msg_file).map_err(|e| e.to_string())?;
+
+    crack_repeating_key_xor_solutions(&buffer, scorer, min_key_size, max_key_size, no_of_sizes)
+}
+
+/// Crack the XOR encoded buffer by using Hamming distance to obtain
+/// guesses for the key size, then crack the key a byte at a time,
+/// judging candidate decodings with the given scorer
+///
+/// On success, it will return the decoded bytes and their score
+pub fn crack_repeating_key_xor_with_scorer(
+    encoded_msg: &[u8],
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Result<(Vec<u8>, f32), String> {
+    crack_repeating_key_xor_parallel(
+        encoded_msg,
+        scorer,
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+        1,
+    )
+}
+
+/// Returns the same decoded bytes and score as crack_repeating_key_xor_with_scorer,
+/// evaluating the key sizes and cracking the key on up to no_of_threads threads
+///
+/// The result does not depend on the number of threads
+pub fn crack_repeating_key_xor_parallel(
+    encoded_msg: &[u8],
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+    no_of_threads: usize,
+) -> Result<(Vec<u8>, f32), String> {
+    let solutions = crack_repeating_key_xor_solutions_parallel(
+        encoded_msg,
+        scorer,
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+        no_of_threads,
+    )?;
+
+    /* The solutions are ranked, so the first is the most plausible */
+    let best = solutions.into_iter().next().unwrap();
+
+    Ok((best.decoded, best.score))
+}
+
+/// Reads a file of base64 data, which may be split over
+/// several lines, and decodes it into a binary buffer
+pub fn read_base64_file(filename: &str) -> Result<Vec<u8>, String> {
+    /* Get the encoded message into a String */
+    let mut file = match File::open(filename) {
+        Ok(file) => file,
+        Err(e) => return Err(e.to_string()),
+    };
+    let mut file_text = String::new();
+    if let Err(e) = file.read_to_string(&mut file_text) {
+        return Err(e.to_string());
+    };
+
+    /* Newlines are not base64, so remove them */
+    file_text.retain(|c| !c.is_whitespace());
+
+    /* Marshal the base64 string into a binary buffer */
+    base64_to_binary_buf(&file_text)
+}
+
 #[cfg(test)]
 mod tests {
     use hex_to_base64_1::{base64_buf_to_utf8_string, base64_u8_to_utf8_char};
+    use repeating_key_xor_5::multi_key_xor_encode;
+    use single_xor_cipher_3::{FileSignatureScorer, NgramModel};
 
     use super::*;
 
@@ -303,14 +451,6 @@ mod tests {
         assert!(result.is_err());
     }
 
-    #[test]
-    fn base64_invalid_chars() {
-        let out_of_range_values = ['*', ',', '.', ':', '@', '[', '`', '{', '<', '>'];
-        for out_of_range_value in out_of_range_values {
-            assert!(char_to_base64_value(out_of_range_value).is_err());
-        }
-    }
-
     #[test]
     fn base64_convert_chars_and_back() {
         for i in 0..64 {
@@ -325,24 +465,6 @@ mod tests {
         }
     }
 
-    #[test]
-    fn base64_equals_sign_is_none() {
-        let result = char_to_base64_value('=');
-        assert!(result.is_ok());
-        let result = result.unwrap();
-        assert!(result.is_none());
-    }
-
-    #[test]
-    fn base64_detect_invalid_padding() {
-        let test_data = ["=", "a=", "aaaa=", "aaaaa="];
-
-        for invalid_padding in test_data {
-            let buffer = base64_to_binary_buf(invalid_padding);
-            assert!(buffer.is_err());
-        }
-    }
-
     #[test]
     fn base64_to_buf_and_back() {
         let test_data = [
@@ -371,6 +493,136 @@ mod tests {
         let result =
             crack_base64_repeating_key_xor("encoded_data.txt", "sample-text.txt", 2, 40, 3);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), expected_text);
+        assert_eq!(result.unwrap()[0].decoded, expected_text.as_bytes());
+    }
+
+    #[test]
+    fn decode_languages_test() {
+        let english = get_file_character_percentages("sample-text.txt").unwrap();
+        let german = get_file_character_percentages("sample-text-de.txt").unwrap();
+        let languages = [("english", &english), ("german", &german)];
+
+        let mut expected_file = File::open("expected.txt").unwrap();
+        let mut expected_text = String::new();
+        expected_file.read_to_string(&mut expected_text).unwrap();
+
+        let result =
+            crack_base64_repeating_key_xor_languages("encoded_data.txt", &languages, 2, 40, 3);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), (expected_text, "english"));
+
+        let german_text = "Am naechsten Morgen ging die junge Frau frueh aus dem Haus, um auf \
+            dem Markt Brot und Kaese zu kaufen. Die Strassen waren noch leer, und nur ein paar \
+            Katzen schlichen an den Mauern entlang. Als sie an der Kirche vorbeikam, hoerte sie \
+            die Glocken laeuten, und sie blieb einen Moment stehen, um zuzuhoeren. Dann setzte \
+            sie ihren Weg fort und dachte an ihren Bruder, der seit vielen Jahren in einer \
+            fernen Stadt lebte und ihr nur selten einen Brief schrieb.";
+        let encoded = multi_key_xor_encode(german_text.as_bytes(), "Geheim".as_bytes());
+
+        let result = crack_repeating_key_xor_languages(&encoded, &languages, 2, 10, 3);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), (String::from(german_text), "german"));
+    }
+
+    #[test]
+    fn decode_with_file_signature_scorer() {
+        /* Every column of a three byte key is covered by the fixed PDF header bytes */
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        pdf.extend_from_slice(&[0xe2, 0xe3, 0xcf, 0xd3, b'\n']);
+        pdf.extend_from_slice(b"1 0 obj\n<< /Type /Catalog >>\nendobj\ntrailer\n%%EOF\n");
+        let encoded = multi_key_xor_encode(&pdf, &[0x13, 0x37, 0xc0]);
+        let scorer = FileSignatureScorer::new();
+
+        let key = get_sized_key_with_scorer(&encoded, 3, &scorer);
+        assert_eq!(key, Ok(vec![0x13, 0x37, 0xc0]));
+
+        let result = crack_repeating_key_xor_with_scorer(&encoded, &scorer, 3, 3, 1);
+        assert_eq!(result, Ok((pdf, 0.0)));
+    }
+
+    #[test]
+    fn decode_parallel_test() {
+        let mut expected_file = File::open("expected.txt").unwrap();
+        let mut expected_text = String::new();
+        expected_file.read_to_string(&mut expected_text).unwrap();
+
+        /* The result must not depend on the number of threads */
+        for no_of_threads in [2, 3, 8] {
+            let result = crack_base64_repeating_key_xor_parallel(
+                "encoded_data.txt",
+                "sample-text.txt",
+                2,
+                40,
+                3,
+                no_of_threads,
+            );
+            assert_eq!(result.unwrap()[0].decoded, expected_text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn long_key_test() {
+        let plaintext = fs::read("long-text.txt").unwrap();
+        let model = NgramModel::from_files(&["reference-text.txt"], 3).unwrap();
+
+        /*
+         * Key sizes above 255 used to be truncated to a byte. A 512 byte key leaves
+         * under 70 bytes per column, too few for the chi-squared scorer to get every
+         * key byte, but enough for the n-gram model's per byte probabilities
+         */
+        for key_size in [313, 512] {
+            let key: Vec<u8> = (0..key_size)
+                .map(|idx: usize| (idx.wrapping_mul(2654435761) >> 7) as u8)
+                .collect();
+            let encoded = multi_key_xor_encode(&plaintext, &key);
+
+            let sizes = get_likely_key_sizes(&encoded, 2, 600, 3);
+            assert_eq!(sizes[0], key_size);
+
+            let result = crack_repeating_key_xor_solutions_parallel(&encoded, &model, 2, 600, 1, 4);
+            assert!(result.is_ok());
+
+            let solutions = result.unwrap();
+            assert_eq!(solutions[0].key, key);
+            assert_eq!(solutions[0].decoded, plaintext);
+        }
+    }
+
+    #[test]
+    fn decode_raw_latin1() {
+        let expected = fs::read("latin1_expected.bin").unwrap();
+
+        /* The umlauts are not valid UTF-8 in Latin-1, so the chi-squared scorer rejects every key */
+        let reference_percentages = get_file_character_percentages("sample-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+        let result = crack_raw_repeating_key_xor("latin1_encoded.bin", &scorer, 2, 20, 3);
+        assert!(result.is_err());
+
+        let model = NgramModel::from_files(&["sample-text.txt"], 3).unwrap();
+        let result = crack_raw_repeating_key_xor("latin1_encoded.bin", &model, 2, 20, 3);
+        assert!(result.is_ok());
+
+        let solutions = result.unwrap();
+        assert_eq!(solutions[0].key, b"Geheimnis");
+        assert_eq!(solutions[0].decoded, expected);
+
+        assert!(crack_raw_repeating_key_xor("missing.bin", &model, 2, 20, 3).is_err());
+    }
+
+    #[test]
+    fn decode_raw_png() {
+        let expected = fs::read("png_expected.bin").unwrap();
+
+        /* The PNG header fixes the first 16 bytes, so try every key size which it covers */
+        let result =
+            crack_raw_repeating_key_xor("png_encoded.bin", &FileSignatureScorer::new(), 2, 16, 15);
+        assert!(result.is_ok());
+
+        /* Only the right key makes every chunk's CRC check out */
+        let solutions = result.unwrap();
+        assert_eq!(solutions[0].key, [0x5a, 0xc3, 0x17, 0x8e, 0x21, 0xf4, 0x6b]);
+        assert_eq!(solutions[0].decoded, expected);
+        assert_eq!(solutions[0].score, 0.0);
+        assert!(solutions[1..].iter().all(|solution| solution.score > 0.0));
     }
 }
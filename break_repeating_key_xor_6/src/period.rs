@@ -0,0 +1,173 @@
+//! This module reduces recovered keys to their fundamental period
+//!
+//! The key size estimators often favour a multiple of the true key size, and
+//! cracking at a multiple gives a key made of repeats of the true key. Each repeat
+//! is cracked from a fraction of the bytes, so the repeats can disagree, garbling
+//! the plaintext under the ones which went wrong. Re-cracking at the shorter period
+//! pools the columns of every repeat, which gives each key byte more to go on
+
+use single_xor_cipher_3::{PlaintextScorer, SingleByteXor, SmallKeyspaceCipher};
+
+use crate::solution::{
+    crack_sized_key_columns, decrypt_periodic, PeriodicSolution, RepeatingXorSolution,
+};
+
+/// Least fraction of the key bytes which must equal the byte one period
+/// earlier, for the key to be treated as a near-repetition of a shorter key
+pub const MIN_PERIOD_AGREEMENT: f64 = 0.5;
+
+/// Returns the fraction of the key bytes after the first period
+/// which equal the byte one period earlier
+///
+/// A key always agrees with itself at its own length or longer
+pub fn get_period_agreement<K: PartialEq>(key: &[K], period: usize) -> f64 {
+    if period == 0 {
+        return 0.0;
+    }
+    if period >= key.len() {
+        return 1.0;
+    }
+
+    let agreeing = key[period..]
+        .iter()
+        .zip(key)
+        .filter(|(byte, earlier)| byte == earlier)
+        .count();
+
+    agreeing as f64 / (key.len() - period) as f64
+}
+
+/// Returns the proper divisors of the key's length at which the key
+/// is at least a near-repetition, shortest first
+pub fn get_candidate_periods<K: PartialEq>(key: &[K], min_agreement: f64) -> Vec<usize> {
+    (1..key.len())
+        .filter(|&period| key.len().is_multiple_of(period))
+        .filter(|&period| get_period_agreement(key, period) >= min_agreement)
+        .collect()
+}
+
+/// Returns the solution with its key reduced to the shortest period
+/// which the key nearly repeats at, re-cracking the key at that period
+///
+/// A period is only used if the message its key decodes to scores no worse,
+/// so a key which merely happens to repeat some bytes is left alone
+pub fn reduce_to_fundamental_period(
+    encoded_msg: &[u8],
+    solution: RepeatingXorSolution,
+    scorer: &dyn PlaintextScorer,
+) -> RepeatingXorSolution {
+    reduce_to_fundamental_period_parallel(encoded_msg, solution, scorer, 1)
+}
+
+/// Returns the same solution as reduce_to_fundamental_period,
+/// re-cracking the columns of the key on up to no_of_threads threads
+pub fn reduce_to_fundamental_period_parallel(
+    encoded_msg: &[u8],
+    solution: RepeatingXorSolution,
+    scorer: &dyn PlaintextScorer,
+    no_of_threads: usize,
+) -> RepeatingXorSolution {
+    reduce_periodic_solution(&SingleByteXor, encoded_msg, solution, scorer, no_of_threads)
+}
+
+/// Reduces the key of a solution to any cipher as reduce_to_fundamental_period does
+pub(crate) fn reduce_periodic_solution<C: SmallKeyspaceCipher>(
+    cipher: &C,
+    encoded_msg: &[u8],
+    solution: PeriodicSolution<C::Key>,
+    scorer: &dyn PlaintextScorer,
+    no_of_threads: usize,
+) -> PeriodicSolution<C::Key> {
+    let mut solution = solution;
+
+    /* A reduced key may itself repeat, so keep going until no shorter period is accepted */
+    'reduce: loop {
+        for period in get_candidate_periods(&solution.key, MIN_PERIOD_AGREEMENT) {
+            let (key, column_confidences) =
+                match crack_sized_key_columns(cipher, encoded_msg, period, scorer, no_of_threads) {
+                    Ok(result) => result,
+                    Err(_) => continue,
+                };
+
+            let decoded = decrypt_periodic(cipher, encoded_msg, &key);
+            let score = match scorer.score(&decoded) {
+                Some(score) => score,
+                None => continue,
+            };
+
+            if score <= solution.score {
+                solution = PeriodicSolution {
+                    key,
+                    decoded,
+                    score,
+                    column_confidences,
+                };
+                continue 'reduce;
+            }
+        }
+
+        return solution;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crack_repeating_key_xor_solutions, get_sized_key_with_scorer, read_base64_file};
+    use repeating_key_xor_5::multi_key_xor_encode;
+    use single_xor_cipher_3::{get_file_character_percentages, ChiSquaredScorer};
+
+    #[test]
+    fn period_agreement() {
+        assert_eq!(get_period_agreement(b"ICEICEICE", 3), 1.0);
+        assert_eq!(get_period_agreement(b"ICEICF", 3), 2.0 / 3.0);
+        assert_eq!(get_period_agreement(b"ICE", 3), 1.0);
+        assert_eq!(get_period_agreement(b"ICE", 0), 0.0);
+
+        assert_eq!(get_candidate_periods(b"ICEICEICEICE", 1.0), vec![3, 6]);
+        assert_eq!(get_candidate_periods(b"ICEICF", 0.5), vec![3]);
+        assert!(get_candidate_periods(b"ICE", 0.5).is_empty());
+    }
+
+    #[test]
+    fn doubled_key_is_reduced() {
+        let encoded = read_base64_file("encoded_data.txt").unwrap();
+        let reference_percentages = get_file_character_percentages("sample-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+
+        /* Cracked at twice its size, the key comes out as two copies of itself */
+        let doubled_key = get_sized_key_with_scorer(&encoded, 58, &scorer).unwrap();
+        assert_eq!(
+            get_candidate_periods(&doubled_key, MIN_PERIOD_AGREEMENT)[0],
+            29
+        );
+
+        let result = crack_repeating_key_xor_solutions(&encoded, &scorer, 58, 58, 1);
+        assert!(result.is_ok());
+
+        let solutions = result.unwrap();
+        assert_eq!(solutions[0].key, b"Terminator X: Bring the noise");
+        assert_eq!(solutions[0].column_confidences.len(), 29);
+    }
+
+    #[test]
+    fn repeated_key_bytes_are_kept() {
+        let reference_percentages = get_file_character_percentages("reference-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+
+        let msg = "With malice toward none, with charity for all, with firmness in the right as \
+            God gives us to see the right, let us strive on to finish the work we are in, to bind \
+            up the nation's wounds, to care for him who shall have borne the battle and for his \
+            widow and his orphan, to do all which may achieve and cherish a just and lasting peace \
+            among ourselves and with all nations.";
+        let key = [0x11, 0x5a, 0x11, 0x3c];
+        let encoded = multi_key_xor_encode(msg.as_bytes(), &key);
+
+        /* Half the key repeats at period 2, but the 2 byte key decodes to nonsense */
+        assert_eq!(get_candidate_periods(&key, MIN_PERIOD_AGREEMENT), vec![2]);
+
+        let result = crack_repeating_key_xor_solutions(&encoded, &scorer, 4, 4, 1);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap()[0].key, key);
+    }
+}
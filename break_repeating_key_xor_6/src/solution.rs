@@ -0,0 +1,328 @@
+//! This module describes a cracked repeating key in full, so that the
+//! caller can see how sure the cracker was of each key byte, and what the
+//! other key sizes it tried decoded to
+//!
+//! Each key byte is cracked as a single byte XOR cipher on its own column of the
+//! message. The gap between the best and second best score for a column says how
+//! clearly its key byte won, so a small gap marks a key byte worth checking by hand
+//!
+//! The columns are cracked with any small keyspace cipher, so the same code also
+//! breaks ciphers such as Vigenère, whose key is a repeating series of shifts
+
+use std::cmp::max;
+
+use crypto_utilities::parallel::parallel_map;
+use single_xor_cipher_3::{
+    crack_small_keyspace_column, PlaintextScorer, SingleByteXor, SmallKeyspaceCipher,
+};
+
+use crate::{get_likely_key_sizes, period::reduce_periodic_solution};
+
+/// A key recovered for one key size, and the message it decrypts to, where
+/// each element of the key is a key of the cipher applied to one column
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodicSolution<K> {
+    /// The recovered key
+    pub key: Vec<K>,
+    /// The message obtained by decrypting with the key
+    pub decoded: Vec<u8>,
+    /// The scorer's judgement of the decoded message (lower is better)
+    pub score: f32,
+    /// For each key element, how much worse the second best key for its column
+    /// scored, or None if the scorer rejected every other key
+    pub column_confidences: Vec<Option<f32>>,
+}
+
+/// A repeating XOR key recovered for one key size, and the message it decodes to
+pub type RepeatingXorSolution = PeriodicSolution<u8>;
+
+impl<K> PeriodicSolution<K> {
+    /// Returns the size of the recovered key
+    pub fn key_size(&self) -> usize {
+        self.key.len()
+    }
+
+    /// Returns the positions of the key elements whose column was won by less
+    /// than the given score gap, least certain first
+    ///
+    /// Columns where every other key was rejected are never uncertain
+    pub fn uncertain_columns(&self, min_score_gap: f32) -> Vec<usize> {
+        let mut uncertain: Vec<(usize, f32)> = self
+            .column_confidences
+            .iter()
+            .enumerate()
+            .filter_map(|(column, gap)| {
+                gap.filter(|&gap| gap < min_score_gap)
+                    .map(|gap| (column, gap))
+            })
+            .collect();
+        uncertain.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        uncertain.into_iter().map(|(column, _)| column).collect()
+    }
+}
+
+/// A cracked key, along with how clearly each of its elements beat the rest
+pub(crate) type CrackedKey<K> = (Vec<K>, Vec<Option<f32>>);
+
+/// A cracked key element, along with how clearly it beat the rest
+pub(crate) type CrackedKeyByte<K> = (K, Option<f32>);
+
+/// Cracks the key for the given key size a column at a time, returning
+/// the key along with how clearly each key element beat the rest
+pub(crate) fn crack_sized_key_columns<C: SmallKeyspaceCipher>(
+    cipher: &C,
+    encoded_msg: &[u8],
+    key_size: usize,
+    scorer: &dyn PlaintextScorer,
+    no_of_threads: usize,
+) -> Result<CrackedKey<C::Key>, String> {
+    if key_size == 0 {
+        return Err(String::from("Cannot crack a key of size zero"));
+    }
+
+    let key_byte_nos: Vec<usize> = (0..key_size).collect();
+    let key_bytes = crack_key_columns(
+        cipher,
+        encoded_msg,
+        key_size,
+        &key_byte_nos,
+        scorer,
+        no_of_threads,
+    )?;
+
+    Ok(key_bytes.into_iter().unzip())
+}
+
+/// Cracks the given key elements of a key of the given size, returning each
+/// key element along with how clearly it beat the rest, in the order given
+pub(crate) fn crack_key_columns<C: SmallKeyspaceCipher>(
+    cipher: &C,
+    encoded_msg: &[u8],
+    key_size: usize,
+    key_byte_nos: &[usize],
+    scorer: &dyn PlaintextScorer,
+    no_of_threads: usize,
+) -> Result<Vec<CrackedKeyByte<C::Key>>, String> {
+    /*
+     * Since the key repeats, we can crack it an element
+     * at a time, by collecting all the bytes which that
+     * element of the key will apply to, then acting as
+     * if it was a cipher with a single small key
+     */
+    let key_bytes = parallel_map(key_byte_nos, no_of_threads, |&key_byte_no| {
+        let column: Vec<u8> = encoded_msg
+            .iter()
+            .skip(key_byte_no)
+            .step_by(key_size)
+            .copied()
+            .collect();
+
+        /* The scorer is told where the column sits in the message, so it can use positional checks */
+        let candidates =
+            crack_small_keyspace_column(cipher, &column, key_byte_no, key_size, scorer, 1)?;
+        Ok((candidates[0].key, candidates[0].score_gap))
+    });
+
+    /* Collecting in column order reports the first column which could not be cracked */
+    key_bytes.into_iter().collect()
+}
+
+/// Returns the message decrypted with the repeating key, each
+/// element of which decrypts the bytes of its own column
+pub(crate) fn decrypt_periodic<C: SmallKeyspaceCipher>(
+    cipher: &C,
+    encoded_msg: &[u8],
+    key: &[C::Key],
+) -> Vec<u8> {
+    let mut decoded = encoded_msg.to_vec();
+
+    for (key_byte_no, &key_byte) in key.iter().enumerate() {
+        let mut column: Vec<u8> = decoded
+            .iter()
+            .skip(key_byte_no)
+            .step_by(key.len())
+            .copied()
+            .collect();
+        cipher.decrypt_in_place(key_byte, &mut column);
+
+        for (byte, decrypted) in decoded
+            .iter_mut()
+            .skip(key_byte_no)
+            .step_by(key.len())
+            .zip(column)
+        {
+            *byte = decrypted;
+        }
+    }
+
+    decoded
+}
+
+/// Cracks the key for each of the likely key sizes, and returns a solution for
+/// each whose decrypted message the scorer accepts, most plausible first
+///
+/// Each key is reduced to its fundamental period, and a key which more than
+/// one key size reduces to is only returned once
+pub(crate) fn crack_periodic_solutions<C: SmallKeyspaceCipher>(
+    cipher: &C,
+    encoded_msg: &[u8],
+    likely_key_sizes: &[usize],
+    scorer: &dyn PlaintextScorer,
+    no_of_threads: usize,
+) -> Vec<PeriodicSolution<C::Key>> {
+    /* Share the threads between the key sizes, and the columns of each key */
+    let threads_per_key_size = max(1, no_of_threads / max(1, likely_key_sizes.len()));
+
+    /*
+     * For each key size, get the most likely key, and score the message it decrypts to,
+     * skipping key sizes for which a key could not be obtained, or whose decrypted
+     * message the scorer rejects
+     */
+    let attempts = parallel_map(likely_key_sizes, no_of_threads, |&key_size| {
+        let (key, column_confidences) =
+            crack_sized_key_columns(cipher, encoded_msg, key_size, scorer, threads_per_key_size)
+                .ok()?;
+
+        let decoded = decrypt_periodic(cipher, encoded_msg, &key);
+        let score = scorer.score(&decoded)?;
+
+        let solution = PeriodicSolution {
+            key,
+            decoded,
+            score,
+            column_confidences,
+        };
+
+        /* A multiple of the key size gives repeats of the key, so report the shortest key */
+        Some(reduce_periodic_solution(
+            cipher,
+            encoded_msg,
+            solution,
+            scorer,
+            threads_per_key_size,
+        ))
+    });
+
+    /* The sort is stable, so ties keep the order of the likely key sizes */
+    let mut solutions: Vec<PeriodicSolution<C::Key>> = attempts.into_iter().flatten().collect();
+    solutions.sort_by(|a, b| a.score.total_cmp(&b.score));
+
+    /* Key sizes which reduce to the same key give the same solution, so only keep its first */
+    let mut seen_keys: Vec<Vec<C::Key>> = Vec::new();
+    solutions.retain(|solution| {
+        if seen_keys.contains(&solution.key) {
+            return false;
+        }
+        seen_keys.push(solution.key.clone());
+        true
+    });
+
+    solutions
+}
+
+/// Crack the XOR encoded buffer by using Hamming distance to obtain
+/// guesses for the key size, then crack the key for each of them
+/// a byte at a time, judging candidate decodings with the given scorer
+///
+/// On success, it will return a solution for each key size whose decoded
+/// message the scorer accepts, most plausible first. Each key is reduced to
+/// its fundamental period, so a key is only returned once
+pub fn crack_repeating_key_xor_solutions(
+    encoded_msg: &[u8],
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Result<Vec<RepeatingXorSolution>, String> {
+    crack_repeating_key_xor_solutions_parallel(
+        encoded_msg,
+        scorer,
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+        1,
+    )
+}
+
+/// Returns the same solutions as crack_repeating_key_xor_solutions,
+/// evaluating the key sizes and cracking the keys on up to no_of_threads threads
+///
+/// The result does not depend on the number of threads
+pub fn crack_repeating_key_xor_solutions_parallel(
+    encoded_msg: &[u8],
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+    no_of_threads: usize,
+) -> Result<Vec<RepeatingXorSolution>, String> {
+    /* Use the Hamming distances to get the likely key sizes */
+    let likely_key_sizes =
+        get_likely_key_sizes(encoded_msg, min_key_size, max_key_size, no_of_sizes);
+
+    let solutions = crack_periodic_solutions(
+        &SingleByteXor,
+        encoded_msg,
+        &likely_key_sizes,
+        scorer,
+        no_of_threads,
+    );
+
+    if solutions.is_empty() {
+        return Err(String::from("Could not decode the given buffer."));
+    }
+
+    Ok(solutions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crack_base64_repeating_key_xor, read_base64_file};
+    use single_xor_cipher_3::{get_file_character_percentages, ChiSquaredScorer};
+
+    #[test]
+    fn solution_details() {
+        let result =
+            crack_base64_repeating_key_xor("encoded_data.txt", "sample-text.txt", 2, 40, 3);
+        assert!(result.is_ok());
+
+        let solutions = result.unwrap();
+        assert_eq!(solutions.len(), 3);
+        assert!(solutions
+            .windows(2)
+            .all(|pair| pair[0].score <= pair[1].score));
+
+        let best = &solutions[0];
+        assert_eq!(best.key, b"Terminator X: Bring the noise");
+        assert_eq!(best.key_size(), 29);
+        assert_eq!(best.column_confidences.len(), 29);
+        assert!(best
+            .column_confidences
+            .iter()
+            .all(|gap| gap.is_some_and(|gap| gap > 0.0)));
+
+        /* Every column is uncertain under a large enough threshold, least certain first */
+        let uncertain = best.uncertain_columns(f32::INFINITY);
+        assert_eq!(uncertain.len(), 29);
+        let gaps: Vec<f32> = uncertain
+            .iter()
+            .map(|&column| best.column_confidences[column].unwrap())
+            .collect();
+        assert!(gaps.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(best.uncertain_columns(0.0).is_empty());
+
+        /* The alternatives use the other likely key sizes */
+        assert!(solutions[1..]
+            .iter()
+            .all(|solution| solution.key_size() != 29));
+
+        /* The solutions must not depend on the number of threads */
+        let encoded = read_base64_file("encoded_data.txt").unwrap();
+        let reference_percentages = get_file_character_percentages("sample-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+        let parallel = crack_repeating_key_xor_solutions_parallel(&encoded, &scorer, 2, 40, 3, 4);
+        assert_eq!(parallel, Ok(solutions));
+    }
+}
@@ -0,0 +1,330 @@
+//! This module encrypts and cracks the classical polyalphabetic ciphers,
+//! Vigenère, Beaufort and variant Beaufort, over the letters A to Z
+//!
+//! Only ASCII letters are enciphered, keeping their case, and the key only moves
+//! on at each letter, so spaces and punctuation pass through unchanged. Once the
+//! other characters are set aside, the letters are a repeating key cipher just
+//! like repeating XOR. Each cipher is a small keyspace cipher over single letters,
+//! so the letters are cracked by the same code as repeating XOR, with each column
+//! trying 26 key letters instead of 256 bytes
+
+use single_xor_cipher_3::{map_letters, PlaintextScorer, SmallKeyspaceCipher};
+
+use crate::{
+    get_likely_key_sizes_with_estimators, solution::crack_periodic_solutions,
+    DEFAULT_KEY_SIZE_ESTIMATORS,
+};
+
+/// Number of letters in the alphabet
+const ALPHABET_LEN: u8 = 26;
+
+/// The ways a key letter can be combined with a message letter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolyalphabeticCipher {
+    /// The key letter is added to the plaintext letter
+    Vigenere,
+    /// The plaintext letter is subtracted from the key letter,
+    /// so encrypting and decrypting are the same
+    Beaufort,
+    /// The key letter is subtracted from the plaintext letter,
+    /// which undoes Vigenère encryption
+    VariantBeaufort,
+}
+
+impl PolyalphabeticCipher {
+    /// Every supported cipher
+    pub const ALL: [PolyalphabeticCipher; 3] = [
+        PolyalphabeticCipher::Vigenere,
+        PolyalphabeticCipher::Beaufort,
+        PolyalphabeticCipher::VariantBeaufort,
+    ];
+
+    /// Encrypts one letter, where letters and key letters are numbered
+    /// from 0 for A to 25 for Z, and larger numbers wrap around
+    pub fn encrypt_letter(&self, letter: u8, key_letter: u8) -> u8 {
+        let (letter, key_letter) = (letter % ALPHABET_LEN, key_letter % ALPHABET_LEN);
+
+        match self {
+            Self::Vigenere => (letter + key_letter) % ALPHABET_LEN,
+            Self::Beaufort => (ALPHABET_LEN + key_letter - letter) % ALPHABET_LEN,
+            Self::VariantBeaufort => (ALPHABET_LEN + letter - key_letter) % ALPHABET_LEN,
+        }
+    }
+
+    /// Decrypts one letter, where letters and key letters are numbered
+    /// from 0 for A to 25 for Z, and larger numbers wrap around
+    pub fn decrypt_letter(&self, letter: u8, key_letter: u8) -> u8 {
+        match self {
+            Self::Vigenere => Self::VariantBeaufort.encrypt_letter(letter, key_letter),
+            Self::Beaufort => Self::Beaufort.encrypt_letter(letter, key_letter),
+            Self::VariantBeaufort => Self::Vigenere.encrypt_letter(letter, key_letter),
+        }
+    }
+}
+
+/// Each key is a single key letter, numbered from 0 for A to 25 for Z, which is
+/// applied to every ASCII letter keeping its case, leaving every other byte alone
+impl SmallKeyspaceCipher for PolyalphabeticCipher {
+    type Key = u8;
+
+    fn keys(&self) -> Vec<u8> {
+        (0..ALPHABET_LEN).collect()
+    }
+
+    fn encrypt_in_place(&self, key: u8, msg: &mut [u8]) {
+        map_letters(msg, |letter| self.encrypt_letter(letter, key));
+    }
+
+    fn decrypt_in_place(&self, key: u8, encrypted_msg: &mut [u8]) {
+        map_letters(encrypted_msg, |letter| self.decrypt_letter(letter, key));
+    }
+}
+
+/// A key recovered for one key size, and the plaintext it decrypts to
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyalphabeticSolution {
+    /// The recovered key, in upper case
+    pub key: String,
+    /// The message obtained by decrypting with the key
+    pub plaintext: String,
+    /// The scorer's judgement of the plaintext's letters (lower is better)
+    pub score: f32,
+}
+
+/// Returns the message encrypted with the key, which must be made of letters
+pub fn polyalphabetic_encrypt(
+    msg: &str,
+    key: &str,
+    cipher: PolyalphabeticCipher,
+) -> Result<String, String> {
+    let key_letters = get_key_letters(key)?;
+    Ok(apply_key(msg, &key_letters, |letter, key_letter| {
+        cipher.encrypt_letter(letter, key_letter)
+    }))
+}
+
+/// Returns the message decrypted with the key, which must be made of letters
+pub fn polyalphabetic_decrypt(
+    encrypted_msg: &str,
+    key: &str,
+    cipher: PolyalphabeticCipher,
+) -> Result<String, String> {
+    let key_letters = get_key_letters(key)?;
+    Ok(apply_key(
+        encrypted_msg,
+        &key_letters,
+        |letter, key_letter| cipher.decrypt_letter(letter, key_letter),
+    ))
+}
+
+/// Crack the encrypted message by estimating the key size from its letters,
+/// then cracking the key a letter at a time, judging candidate plaintexts with
+/// the given scorer, which should only look at letters
+///
+/// On success, it will return a solution for each likely key size whose plaintext
+/// the scorer accepts, most plausible first, with each key reduced to its
+/// fundamental period
+pub fn crack_polyalphabetic(
+    encrypted_msg: &str,
+    cipher: PolyalphabeticCipher,
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+) -> Result<Vec<PolyalphabeticSolution>, String> {
+    crack_polyalphabetic_parallel(
+        encrypted_msg,
+        cipher,
+        scorer,
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+        1,
+    )
+}
+
+/// Returns the same solutions as crack_polyalphabetic,
+/// trying the key sizes on up to no_of_threads threads
+///
+/// The result does not depend on the number of threads
+pub fn crack_polyalphabetic_parallel(
+    encrypted_msg: &str,
+    cipher: PolyalphabeticCipher,
+    scorer: &dyn PlaintextScorer,
+    min_key_size: usize,
+    max_key_size: usize,
+    no_of_sizes: usize,
+    no_of_threads: usize,
+) -> Result<Vec<PolyalphabeticSolution>, String> {
+    /* Set aside everything but the letters, which is where the key applies */
+    let letters: Vec<u8> = encrypted_msg
+        .bytes()
+        .filter(u8::is_ascii_alphabetic)
+        .map(|byte| byte.to_ascii_uppercase())
+        .collect();
+
+    let likely_key_sizes = get_likely_key_sizes_with_estimators(
+        &letters,
+        &DEFAULT_KEY_SIZE_ESTIMATORS,
+        min_key_size,
+        max_key_size,
+        no_of_sizes,
+    );
+
+    let solutions =
+        crack_periodic_solutions(&cipher, &letters, &likely_key_sizes, scorer, no_of_threads);
+
+    if solutions.is_empty() {
+        return Err(String::from("Could not decrypt the given message."));
+    }
+
+    /* Put the key letters back to the rest of the message, to decrypt it in full */
+    solutions
+        .into_iter()
+        .map(|solution| {
+            let key: String = solution
+                .key
+                .iter()
+                .map(|&key_letter| (key_letter + b'A') as char)
+                .collect();
+            let plaintext = polyalphabetic_decrypt(encrypted_msg, &key, cipher)?;
+
+            Ok(PolyalphabeticSolution {
+                key,
+                plaintext,
+                score: solution.score,
+            })
+        })
+        .collect()
+}
+
+/// Returns the key's letters numbered from 0 for A to 25 for Z,
+/// or an error if the key is empty or has anything but letters
+fn get_key_letters(key: &str) -> Result<Vec<u8>, String> {
+    if key.is_empty() {
+        return Err(String::from("The key must have at least one letter"));
+    }
+    if !key.bytes().all(|byte| byte.is_ascii_alphabetic()) {
+        return Err(String::from(
+            "The key must only be made of the letters A to Z",
+        ));
+    }
+
+    Ok(key
+        .bytes()
+        .map(|byte| byte.to_ascii_uppercase() - b'A')
+        .collect())
+}
+
+/// Combines each letter of the message with the next letter of the key,
+/// keeping its case, and passes every other character through
+fn apply_key(msg: &str, key_letters: &[u8], combine: impl Fn(u8, u8) -> u8) -> String {
+    let mut key_letters = key_letters.iter().cycle();
+
+    msg.chars()
+        .map(|c| {
+            if !c.is_ascii_alphabetic() {
+                return c;
+            }
+
+            let base = if c.is_ascii_uppercase() { b'A' } else { b'a' };
+            let key_letter = *key_letters.next().unwrap();
+            (combine(c as u8 - base, key_letter) + base) as char
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use single_xor_cipher_3::{
+        get_file_normalized_character_percentages, ChiSquaredScorer, Normalization,
+    };
+
+    #[test]
+    fn known_ciphertexts() {
+        let cases = [
+            (PolyalphabeticCipher::Vigenere, "LXFOPVEFRNHR"),
+            (PolyalphabeticCipher::Beaufort, "LLTOLBETLNPR"),
+            (PolyalphabeticCipher::VariantBeaufort, "PPHMPZWHPNLJ"),
+        ];
+
+        for (cipher, expected) in cases {
+            let encrypted = polyalphabetic_encrypt("ATTACKATDAWN", "LEMON", cipher);
+            assert_eq!(encrypted, Ok(String::from(expected)));
+
+            let decrypted = polyalphabetic_decrypt(expected, "lemon", cipher);
+            assert_eq!(decrypted, Ok(String::from("ATTACKATDAWN")));
+        }
+
+        /* Case and punctuation are kept, and the key only moves on at letters */
+        let encrypted =
+            polyalphabetic_encrypt("Attack at dawn!", "LEMON", PolyalphabeticCipher::Vigenere);
+        assert_eq!(encrypted, Ok(String::from("Lxfopv ef rnhr!")));
+
+        /* As a small keyspace cipher, each key is a single letter of the key */
+        for cipher in PolyalphabeticCipher::ALL {
+            let mut encrypted = b"Attack at dawn!".to_vec();
+            cipher.encrypt_in_place(b'L' - b'A', &mut encrypted);
+            assert_eq!(
+                polyalphabetic_encrypt("Attack at dawn!", "L", cipher),
+                Ok(String::from_utf8(encrypted.clone()).unwrap())
+            );
+
+            cipher.decrypt_in_place(b'L' - b'A', &mut encrypted);
+            assert_eq!(encrypted, b"Attack at dawn!");
+        }
+
+        assert!(polyalphabetic_encrypt("abc", "", PolyalphabeticCipher::Vigenere).is_err());
+        assert!(polyalphabetic_encrypt("abc", "LE MON", PolyalphabeticCipher::Vigenere).is_err());
+    }
+
+    #[test]
+    fn large_letters_wrap_around() {
+        for cipher in PolyalphabeticCipher::ALL {
+            for letter in 0..=u8::MAX {
+                for key_letter in 0..=u8::MAX {
+                    let encrypted = cipher.encrypt_letter(letter, key_letter);
+                    assert_eq!(
+                        encrypted,
+                        cipher.encrypt_letter(letter % ALPHABET_LEN, key_letter % ALPHABET_LEN)
+                    );
+                    assert!(encrypted < ALPHABET_LEN);
+                    assert_eq!(
+                        cipher.decrypt_letter(encrypted, key_letter),
+                        letter % ALPHABET_LEN
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crack_each_cipher() {
+        let reference_percentages = get_file_normalized_character_percentages(
+            "long-text.txt",
+            &Normalization::letters_only(),
+        )
+        .unwrap();
+        let scorer = ChiSquaredScorer::with_normalization(
+            &reference_percentages,
+            Normalization::letters_only(),
+        );
+        let msg = std::fs::read_to_string("sample-text.txt").unwrap();
+
+        for cipher in PolyalphabeticCipher::ALL {
+            let encrypted = polyalphabetic_encrypt(&msg, "LEMONADE", cipher).unwrap();
+
+            let result = crack_polyalphabetic(&encrypted, cipher, &scorer, 2, 20, 3);
+            assert!(result.is_ok());
+
+            let solutions = result.unwrap();
+            assert_eq!(solutions[0].key, "LEMONADE");
+            assert_eq!(solutions[0].plaintext, msg);
+
+            /* Every thread count should give the same ranking */
+            let parallel = crack_polyalphabetic_parallel(&encrypted, cipher, &scorer, 2, 20, 3, 3);
+            assert_eq!(parallel, Ok(solutions));
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! This module cracks repeating XOR by trying every key in a wordlist
+//!
+//! Short messages do not have enough bytes per column for the key size
+//! estimators or the frequency analysis to work, but people tend to pick words
+//! and passphrases as keys. Each word is tried as it is, and optionally with the
+//! changes of case and letter substitutions people make to words to disguise them
+
+use std::{collections::HashSet, fs};
+
+use crypto_utilities::parallel::parallel_map;
+use repeating_key_xor_5::multi_key_xor_encode;
+use single_xor_cipher_3::PlaintextScorer;
+
+/// Number of words each thread tries per batch when attacking in parallel
+const WORDS_PER_THREAD: usize = 1024;
+
+/// Most letters of a word which are substituted independently, since every
+/// combination is tried. Words with more substitutable letters only have
+/// every substitution made at once
+const MAX_LEETSPEAK_LETTERS: usize = 8;
+
+/// Which variations of each word in the wordlist are tried as keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WordlistMutations {
+    /// Also try the word in lower case, upper case and with a capital first letter
+    pub case: bool,
+    /// Also try the word with letters swapped for look-alike digits and symbols,
+    /// such as "p4ssw0rd" for "password"
+    pub leetspeak: bool,
+}
+
+impl WordlistMutations {
+    /// Returns mutations which try every variation
+    pub fn all() -> Self {
+        Self {
+            case: true,
+            leetspeak: true,
+        }
+    }
+}
+
+/// A key from the wordlist, and the message it decodes to
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordlistCandidate {
+    /// The key, after any mutations
+    pub key: Vec<u8>,
+    /// The message obtained by decoding with the key
+    pub decoded: Vec<u8>,
+    /// The scorer's judgement of the decoded message (lower is better)
+    pub score: f32,
+}
+
+/// Reads a wordlist file with one word per line, skipping empty lines
+///
+/// Words are kept as bytes, since wordlists are often not valid UTF-8
+pub fn read_wordlist(filename: &str) -> Result<Vec<Vec<u8>>, String> {
+    let contents = fs::read(filename).map_err(|e| e.to_string())?;
+
+    Ok(contents
+        .split(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_vec())
+        .collect())
+}
+
+/// Returns the word and each of its variations, without repeats,
+/// starting with the word as it is
+pub fn get_key_mutations(word: &[u8], mutations: &WordlistMutations) -> Vec<Vec<u8>> {
+    let mut cased = vec![word.to_vec()];
+    if mutations.case {
+        let mut capitalised = word.to_ascii_lowercase();
+        if let Some(first) = capitalised.first_mut() {
+            first.make_ascii_uppercase();
+        }
+
+        cased.push(word.to_ascii_lowercase());
+        cased.push(word.to_ascii_uppercase());
+        cased.push(capitalised);
+    }
+
+    let mut keys = Vec::new();
+    for key in cased {
+        if mutations.leetspeak {
+            keys.extend(get_leetspeak_variations(&key));
+        } else {
+            keys.push(key);
+        }
+    }
+
+    /* Keep the first of any repeats, so the word as it is comes first */
+    let mut seen = HashSet::new();
+    keys.retain(|key| seen.insert(key.clone()));
+
+    keys
+}
+
+/// Tries each word in the wordlist, and its variations, as the key,
+/// and returns up to no_of_candidates of them ranked by the scorer
+///
+/// Keys whose decoded message the scorer rejects are not ranked, and ties
+/// in the score are broken by the position of the key in the wordlist
+pub fn crack_repeating_key_xor_with_wordlist(
+    encoded_msg: &[u8],
+    words: &[Vec<u8>],
+    mutations: &WordlistMutations,
+    scorer: &dyn PlaintextScorer,
+    no_of_candidates: usize,
+) -> Result<Vec<WordlistCandidate>, String> {
+    crack_repeating_key_xor_with_wordlist_parallel(
+        encoded_msg,
+        words,
+        mutations,
+        scorer,
+        no_of_candidates,
+        1,
+    )
+}
+
+/// Returns the same keys as crack_repeating_key_xor_with_wordlist,
+/// trying the words on up to no_of_threads threads
+///
+/// The result does not depend on the number of threads
+pub fn crack_repeating_key_xor_with_wordlist_parallel(
+    encoded_msg: &[u8],
+    words: &[Vec<u8>],
+    mutations: &WordlistMutations,
+    scorer: &dyn PlaintextScorer,
+    no_of_candidates: usize,
+    no_of_threads: usize,
+) -> Result<Vec<WordlistCandidate>, String> {
+    let mut best: Vec<(Vec<u8>, f32)> = Vec::new();
+
+    /* Work through the wordlist in batches, so only the best keys are held at once */
+    let batch_size = WORDS_PER_THREAD * no_of_threads.max(1);
+    for batch in words.chunks(batch_size) {
+        let attempts = parallel_map(batch, no_of_threads, |word| {
+            get_key_mutations(word, mutations)
+                .into_iter()
+                .filter(|key| !key.is_empty())
+                .filter_map(|key| {
+                    let score = scorer.score(&multi_key_xor_encode(encoded_msg, &key))?;
+                    Some((key, score))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        /*
+         * A key can come from several words, and always scores the same, so only
+         * its first is kept. The sort is stable, so ties keep the wordlist order
+         */
+        let mut seen: HashSet<Vec<u8>> = best.iter().map(|(key, _)| key.clone()).collect();
+        best.extend(
+            attempts
+                .into_iter()
+                .flatten()
+                .filter(|(key, _)| seen.insert(key.clone())),
+        );
+        best.sort_by(|a, b| a.1.total_cmp(&b.1));
+        best.truncate(no_of_candidates);
+    }
+
+    if best.is_empty() {
+        return Err(String::from(
+            "No key in the wordlist decoded the message to a plausible plaintext",
+        ));
+    }
+
+    Ok(best
+        .into_iter()
+        .map(|(key, score)| WordlistCandidate {
+            decoded: multi_key_xor_encode(encoded_msg, &key),
+            key,
+            score,
+        })
+        .collect())
+}
+
+/// Returns the look-alike digit or symbol for a letter, if it has one
+fn get_leetspeak_substitute(byte: u8) -> Option<u8> {
+    match byte.to_ascii_lowercase() {
+        b'a' => Some(b'4'),
+        b'b' => Some(b'8'),
+        b'e' => Some(b'3'),
+        b'g' => Some(b'9'),
+        b'i' => Some(b'1'),
+        b'o' => Some(b'0'),
+        b's' => Some(b'5'),
+        b't' => Some(b'7'),
+        _ => None,
+    }
+}
+
+/// Returns the key with every combination of its substitutable letters
+/// substituted, starting with none and ending with all of them
+fn get_leetspeak_variations(key: &[u8]) -> Vec<Vec<u8>> {
+    let positions: Vec<usize> = (0..key.len())
+        .filter(|&idx| get_leetspeak_substitute(key[idx]).is_some())
+        .collect();
+
+    let substitute = |key: &mut Vec<u8>, idx: usize| {
+        key[idx] = get_leetspeak_substitute(key[idx]).unwrap();
+    };
+
+    if positions.len() > MAX_LEETSPEAK_LETTERS {
+        let mut all = key.to_vec();
+        for &idx in &positions {
+            substitute(&mut all, idx);
+        }
+        return vec![key.to_vec(), all];
+    }
+
+    /* Each bit of the mask says whether one of the letters is substituted */
+    (0..1usize << positions.len())
+        .map(|mask| {
+            let mut variation = key.to_vec();
+            for (bit, &idx) in positions.iter().enumerate() {
+                if mask & (1 << bit) != 0 {
+                    substitute(&mut variation, idx);
+                }
+            }
+            variation
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use single_xor_cipher_3::{get_file_character_percentages, ChiSquaredScorer};
+
+    #[test]
+    fn key_mutations() {
+        let keys = get_key_mutations(b"Pass", &WordlistMutations::default());
+        assert_eq!(keys, vec![b"Pass".to_vec()]);
+
+        let keys = get_key_mutations(
+            b"Pass",
+            &WordlistMutations {
+                case: true,
+                leetspeak: false,
+            },
+        );
+        assert_eq!(
+            keys,
+            vec![b"Pass".to_vec(), b"pass".to_vec(), b"PASS".to_vec()]
+        );
+
+        let keys = get_key_mutations(
+            b"sat",
+            &WordlistMutations {
+                case: false,
+                leetspeak: true,
+            },
+        );
+        let expected: Vec<Vec<u8>> = ["sat", "5at", "s4t", "54t", "sa7", "5a7", "s47", "547"]
+            .iter()
+            .map(|key| key.as_bytes().to_vec())
+            .collect();
+        assert_eq!(keys, expected);
+
+        /* Too many letters to combine, so only all of them are substituted */
+        let keys = get_key_mutations(b"aaaaaaaaa", &WordlistMutations::all());
+        assert_eq!(
+            keys,
+            vec![
+                b"aaaaaaaaa".to_vec(),
+                b"444444444".to_vec(),
+                b"AAAAAAAAA".to_vec(),
+                b"Aaaaaaaaa".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn wordlist_attack() {
+        let reference_percentages = get_file_character_percentages("sample-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+        let words = read_wordlist("wordlist.txt").unwrap();
+
+        let msg = b"Meet me by the old mill at midnight, and bring the map";
+        let encoded = multi_key_xor_encode(msg, b"S3cr3t");
+
+        /* The key is a disguised word, so the plain wordlist does not have it */
+        let result = crack_repeating_key_xor_with_wordlist(
+            &encoded,
+            &words,
+            &WordlistMutations::default(),
+            &scorer,
+            3,
+        );
+        assert!(result.is_err() || result.unwrap()[0].key != b"S3cr3t");
+
+        let result = crack_repeating_key_xor_with_wordlist(
+            &encoded,
+            &words,
+            &WordlistMutations::all(),
+            &scorer,
+            3,
+        );
+        assert!(result.is_ok());
+
+        let candidates = result.unwrap();
+        assert_eq!(candidates[0].key, b"S3cr3t");
+        assert_eq!(candidates[0].decoded, msg);
+        assert!(candidates
+            .windows(2)
+            .all(|pair| pair[0].score <= pair[1].score));
+
+        /* Every thread count should give the same ranking */
+        for no_of_threads in [2, 7] {
+            let parallel = crack_repeating_key_xor_with_wordlist_parallel(
+                &encoded,
+                &words,
+                &WordlistMutations::all(),
+                &scorer,
+                3,
+                no_of_threads,
+            );
+            assert_eq!(parallel, Ok(candidates.clone()));
+        }
+
+        assert!(read_wordlist("missing.txt").is_err());
+    }
+}
@@ -0,0 +1,340 @@
+//! This module provides statistical measures over arbitrary binary data,
+//! which can be used to triage unknown blobs before deciding how to crack them
+//!
+//! As a rough guide, encrypted or compressed data has an entropy close to 8 bits
+//! per byte, an index of coincidence close to 1/256, passes the uniformity test
+//! and has a serial correlation close to zero, while plaintext and data encoded
+//! with a short repeating XOR key do not
+
+/// Number of distinct byte values
+const BYTE_VALUES: usize = 256;
+
+/// A summary of the statistics of a buffer
+#[derive(Debug, Clone, PartialEq)]
+pub struct ByteStatistics {
+    /// Number of bytes analysed
+    pub len: usize,
+    /// Shannon entropy in bits per byte, between 0 and 8
+    pub entropy: f64,
+    /// Probability that two bytes picked at random are equal
+    pub index_of_coincidence: f64,
+    /// Result of testing the byte distribution against the uniform distribution
+    pub uniformity: UniformityTest,
+    /// Correlation between each byte and the next, or None if it is undefined
+    pub serial_correlation: Option<f64>,
+    /// Arithmetic mean of the byte values
+    pub mean: f64,
+}
+
+/// Result of a chi-squared test of a buffer's byte distribution against the uniform distribution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UniformityTest {
+    /// The chi-squared statistic
+    pub chi_squared: f64,
+    /// Degrees of freedom of the test
+    pub degrees_of_freedom: usize,
+    /// Approximate probability of a statistic at least this large if the bytes were uniform
+    pub p_value: f64,
+}
+
+impl UniformityTest {
+    /// Returns true if the hypothesis that the bytes are uniform
+    /// is not rejected at the given significance level
+    pub fn is_uniform(&self, significance: f64) -> bool {
+        self.p_value >= significance
+    }
+}
+
+/// Calculates every statistic in this module over the buffer
+pub fn get_byte_statistics(buf: &[u8]) -> ByteStatistics {
+    let mean = if buf.is_empty() {
+        0.0
+    } else {
+        buf.iter().map(|&byte| byte as f64).sum::<f64>() / buf.len() as f64
+    };
+
+    ByteStatistics {
+        len: buf.len(),
+        entropy: get_shannon_entropy(buf),
+        index_of_coincidence: get_index_of_coincidence(buf),
+        uniformity: get_uniformity_test(buf),
+        serial_correlation: get_serial_correlation(buf),
+        mean,
+    }
+}
+
+/// Returns the number of times each byte value appears in the buffer
+pub fn get_byte_counts(buf: &[u8]) -> [usize; BYTE_VALUES] {
+    let mut counts = [0; BYTE_VALUES];
+    for &byte in buf {
+        counts[byte as usize] += 1;
+    }
+    counts
+}
+
+/// Calculates the Shannon entropy of the buffer in bits per byte
+///
+/// Returns 0 for an empty buffer
+pub fn get_shannon_entropy(buf: &[u8]) -> f64 {
+    entropy_from_counts(&get_byte_counts(buf), buf.len())
+}
+
+/// Calculates the Shannon entropy of each window of window_size bytes,
+/// starting a new window every step bytes
+///
+/// A final window shorter than window_size is not included
+pub fn get_sliding_window_entropy(
+    buf: &[u8],
+    window_size: usize,
+    step: usize,
+) -> Result<Vec<f64>, String> {
+    if window_size == 0 || step == 0 {
+        return Err(String::from(
+            "Window size and step must be greater than zero",
+        ));
+    }
+
+    let mut entropies = Vec::new();
+    if buf.len() < window_size {
+        return Ok(entropies);
+    }
+
+    /* Keep running counts, so each window only costs the bytes which entered and left it */
+    let mut counts = get_byte_counts(&buf[..window_size]);
+    let mut start = 0;
+
+    loop {
+        entropies.push(entropy_from_counts(&counts, window_size));
+
+        let next_start = start + step;
+        if next_start + window_size > buf.len() {
+            break;
+        }
+
+        /* If the windows overlap, slide the counts, otherwise count the next window afresh */
+        if step < window_size {
+            for &byte in &buf[start..next_start] {
+                counts[byte as usize] -= 1;
+            }
+            for &byte in &buf[start + window_size..next_start + window_size] {
+                counts[byte as usize] += 1;
+            }
+        } else {
+            counts = get_byte_counts(&buf[next_start..next_start + window_size]);
+        }
+
+        start = next_start;
+    }
+
+    Ok(entropies)
+}
+
+/// Calculates the index of coincidence of the buffer, which is the
+/// probability that two bytes picked at random without replacement are equal
+///
+/// Returns 0 for buffers with fewer than two bytes
+pub fn get_index_of_coincidence(buf: &[u8]) -> f64 {
+    if buf.len() < 2 {
+        return 0.0;
+    }
+
+    /* Multiply as floats, since the square of a count can overflow a 32 bit usize */
+    let coincidences: f64 = get_byte_counts(buf)
+        .iter()
+        .map(|&count| count as f64 * count.saturating_sub(1) as f64)
+        .sum();
+    let len = buf.len() as f64;
+
+    coincidences / (len * (len - 1.0))
+}
+
+/// Tests the buffer's byte distribution against the uniform distribution with a chi-squared test
+///
+/// The p-value uses the Wilson-Hilferty approximation, which is accurate
+/// for the 255 degrees of freedom of this test
+pub fn get_uniformity_test(buf: &[u8]) -> UniformityTest {
+    let degrees_of_freedom = BYTE_VALUES - 1;
+
+    if buf.is_empty() {
+        return UniformityTest {
+            chi_squared: 0.0,
+            degrees_of_freedom,
+            p_value: 1.0,
+        };
+    }
+
+    let expected = buf.len() as f64 / BYTE_VALUES as f64;
+    let chi_squared: f64 = get_byte_counts(buf)
+        .iter()
+        .map(|&count| {
+            let difference = count as f64 - expected;
+            difference * difference / expected
+        })
+        .sum();
+
+    /* Transform the statistic to an approximately standard normal variable */
+    let k = degrees_of_freedom as f64;
+    let variance = 2.0 / (9.0 * k);
+    let z = ((chi_squared / k).cbrt() - (1.0 - variance)) / variance.sqrt();
+
+    UniformityTest {
+        chi_squared,
+        degrees_of_freedom,
+        p_value: 1.0 - standard_normal_cdf(z),
+    }
+}
+
+/// Calculates the correlation between each byte and the one which follows it,
+/// treating the buffer as circular, between -1 and 1
+///
+/// Returns None for buffers with fewer than two bytes or which contain a single byte value
+pub fn get_serial_correlation(buf: &[u8]) -> Option<f64> {
+    if buf.len() < 2 {
+        return None;
+    }
+
+    let len = buf.len() as f64;
+    let mut sum = 0.0;
+    let mut sum_of_squares = 0.0;
+    let mut sum_of_products = 0.0;
+
+    for (idx, &byte) in buf.iter().enumerate() {
+        let byte = byte as f64;
+        let next = buf[(idx + 1) % buf.len()] as f64;
+
+        sum += byte;
+        sum_of_squares += byte * byte;
+        sum_of_products += byte * next;
+    }
+
+    let denominator = len * sum_of_squares - sum * sum;
+    if denominator == 0.0 {
+        return None;
+    }
+
+    Some((len * sum_of_products - sum * sum) / denominator)
+}
+
+/// Calculates Shannon entropy in bits from counts of each byte value
+fn entropy_from_counts(counts: &[usize], total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / total as f64;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// Approximates the cumulative distribution function of the standard normal distribution
+fn standard_normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / std::f64::consts::SQRT_2))
+}
+
+/// Approximates the error function, with a maximum error of 1.5e-7
+/// (Abramowitz and Stegun, formula 7.1.26)
+fn erf(x: f64) -> f64 {
+    let sign = x.signum();
+    let x = x.abs();
+
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+
+    sign * (1.0 - polynomial * (-x * x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every byte value, each appearing the same number of times
+    fn uniform_bytes() -> Vec<u8> {
+        (0..=255).cycle().take(256 * 16).collect()
+    }
+
+    #[test]
+    fn entropy_extremes() {
+        assert_eq!(get_shannon_entropy(&[]), 0.0);
+        assert_eq!(get_shannon_entropy(&[7; 100]), 0.0);
+        assert_eq!(get_shannon_entropy(b"abab"), 1.0);
+        assert_eq!(get_shannon_entropy(&uniform_bytes()), 8.0);
+    }
+
+    #[test]
+    fn sliding_window_entropy() {
+        let mut buf = vec![0; 64];
+        buf.extend(0..64);
+
+        /* Overlapping and non-overlapping windows should agree with the global entropy of each window */
+        for step in [16, 64, 100] {
+            let result = get_sliding_window_entropy(&buf, 64, step);
+            assert!(result.is_ok());
+
+            let expected: Vec<f64> = (0..=buf.len() - 64)
+                .step_by(step)
+                .map(|start| get_shannon_entropy(&buf[start..start + 64]))
+                .collect();
+            assert_eq!(result.unwrap(), expected);
+        }
+
+        assert_eq!(get_sliding_window_entropy(&buf, 256, 1), Ok(Vec::new()));
+        assert!(get_sliding_window_entropy(&buf, 0, 1).is_err());
+        assert!(get_sliding_window_entropy(&buf, 1, 0).is_err());
+    }
+
+    #[test]
+    fn index_of_coincidence() {
+        assert_eq!(get_index_of_coincidence(b"a"), 0.0);
+        assert_eq!(get_index_of_coincidence(b"aaaa"), 1.0);
+        assert_eq!(get_index_of_coincidence(b"abcd"), 0.0);
+        assert_eq!(get_index_of_coincidence(b"aabb"), 1.0 / 3.0);
+
+        /* Counts above 65535 square to more than a 32 bit usize can hold */
+        assert_eq!(get_index_of_coincidence(&[b'a'; 70_000]), 1.0);
+    }
+
+    #[test]
+    fn uniformity_test() {
+        let uniform = get_uniformity_test(&uniform_bytes());
+        assert_eq!(uniform.chi_squared, 0.0);
+        assert!(uniform.is_uniform(0.01));
+
+        let text = get_uniformity_test(
+            "the quick brown fox jumps over the lazy dog"
+                .repeat(20)
+                .as_bytes(),
+        );
+        assert!(text.p_value < 1e-6);
+        assert!(!text.is_uniform(0.01));
+    }
+
+    #[test]
+    fn serial_correlation() {
+        assert_eq!(get_serial_correlation(&[5]), None);
+        assert_eq!(get_serial_correlation(&[5; 10]), None);
+
+        /* Alternating bytes are perfectly anti-correlated */
+        let alternating: Vec<u8> = [0, 255].repeat(50);
+        assert_eq!(get_serial_correlation(&alternating), Some(-1.0));
+
+        /* A slowly increasing ramp is strongly correlated */
+        let ramp: Vec<u8> = (0..=255).collect();
+        assert!(get_serial_correlation(&ramp).unwrap() > 0.9);
+    }
+
+    #[test]
+    fn byte_statistics_summary() {
+        let statistics = get_byte_statistics(&uniform_bytes());
+        assert_eq!(statistics.len, 4096);
+        assert_eq!(statistics.entropy, 8.0);
+        assert_eq!(statistics.mean, 127.5);
+        assert!(statistics.uniformity.is_uniform(0.01));
+    }
+}
@@ -1,5 +1,9 @@
 //! This crate contains utilities which are common to many solutions in this workspace
 
+pub mod analysis;
+pub mod parallel;
+pub mod xor;
+
 /// Determines if a &str is valid hexadecimal
 pub fn is_valid_hex(buf: &str) -> bool {
     for c in buf.chars() {
@@ -65,6 +69,66 @@ pub fn hex_to_binary_buffer(hex: &str) -> Result<Vec<u8>, String> {
     Ok(result)
 }
 
+/// Converts a base64 character into its base64 numeric value
+pub fn char_to_base64_value(c: char) -> Result<Option<u8>, String> {
+    match c {
+        'A'..='Z' => Ok(Some((c as u32 - 'A' as u32) as u8)),
+        'a'..='z' => Ok(Some((c as u32 - 'a' as u32 + 26) as u8)),
+        '0'..='9' => Ok(Some((c as u32 - '0' as u32 + 52) as u8)),
+        '+' => Ok(Some(62)),
+        '/' => Ok(Some(63)),
+        '=' => Ok(None),
+        _ => Err(String::from("Non base64 character passed")),
+    }
+}
+
+/// Decodes a base64 string into the corresponding binary buffer
+pub fn base64_to_binary_buf(base64: &str) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+
+    /* Perform the marshaling 24 bits at a time, as base64 operates on groups of 24 bits */
+    let mut temp: [u8; 3] = [0; 3];
+
+    for (idx, base64_char) in base64.chars().enumerate() {
+        if let Some(base64_value) = char_to_base64_value(base64_char)? {
+            match idx % 4 {
+                0 => temp[0] = base64_value << 2,
+                1 => {
+                    temp[0] |= base64_value >> 4;
+                    temp[1] = base64_value << 4;
+                }
+                2 => {
+                    temp[1] |= base64_value >> 2;
+                    temp[2] = base64_value << 6;
+                }
+                _ => {
+                    temp[2] |= base64_value;
+                    /* Push bytes into buffer once 24 bits have been processed */
+                    for byte in temp {
+                        buffer.push(byte);
+                    }
+                    /* Reinitialise to zero for next iteration */
+                    temp = [0; 3];
+                }
+            }
+        } else {
+            /* If padding is encountered, push remaining bytes which contain data */
+            match idx % 4 {
+                0 | 1 => return Err("Invalid padding position".to_string()),
+                2 => {
+                    buffer.push(temp[0]);
+                }
+                _ => {
+                    buffer.extend_from_slice(&temp[..2]);
+                }
+            }
+            break;
+        }
+    }
+
+    Ok(buffer)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -127,4 +191,30 @@ mod tests {
         let result = hex_to_binary_buffer("invalid_hex");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn base64_invalid_chars() {
+        let out_of_range_values = ['*', ',', '.', ':', '@', '[', '`', '{', '<', '>'];
+        for out_of_range_value in out_of_range_values {
+            assert!(char_to_base64_value(out_of_range_value).is_err());
+        }
+    }
+
+    #[test]
+    fn base64_equals_sign_is_none() {
+        let result = char_to_base64_value('=');
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn base64_detect_invalid_padding() {
+        let test_data = ["=", "a=", "aaaa=", "aaaaa="];
+
+        for invalid_padding in test_data {
+            let buffer = base64_to_binary_buf(invalid_padding);
+            assert!(buffer.is_err());
+        }
+    }
 }
@@ -0,0 +1,73 @@
+//! This module provides a simple way of spreading work over several threads
+//!
+//! Results are always returned in the order of the inputs, so callers which
+//! reduce them in that order get the same answer regardless of thread count
+
+use std::thread;
+
+/// Applies f to every item, spreading the items over up to no_of_threads
+/// scoped threads, and returns the results in the same order as the items
+///
+/// Each thread is given one contiguous chunk of the items. If no_of_threads
+/// is 0 or 1, or there is only one item, everything runs on the calling thread
+pub fn parallel_map<T, R, F>(items: &[T], no_of_threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let no_of_threads = no_of_threads.clamp(1, items.len().max(1));
+    if no_of_threads == 1 {
+        return items.iter().map(f).collect();
+    }
+
+    /* Round up so that every item is in a chunk */
+    let chunk_size = items.len().div_ceil(no_of_threads);
+    let f = &f;
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+
+        /* Joining in spawn order keeps the results in the order of the items */
+        handles
+            .into_iter()
+            .flat_map(|handle| match handle.join() {
+                Ok(results) => results,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
+    })
+}
+
+/// Returns the number of threads the machine can usefully run in parallel,
+/// or 1 if this cannot be determined
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_map_preserves_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        let expected: Vec<u32> = items.iter().map(|item| item * item).collect();
+
+        for no_of_threads in [0, 1, 2, 3, 7, 64, 2000] {
+            assert_eq!(
+                parallel_map(&items, no_of_threads, |item| item * item),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_map_empty() {
+        let items: [u32; 0] = [];
+        assert_eq!(parallel_map(&items, 4, |item| *item), Vec::<u32>::new());
+    }
+}
@@ -0,0 +1,184 @@
+//! This module provides in-place XOR kernels which process a machine word
+//! at a time, rather than a byte at a time
+//!
+//! The kernels are written in portable Rust over u64 words, which the
+//! compiler is free to vectorise into SIMD instructions where the target
+//! supports them. Bytes which do not fill a whole word are XORed one at a time
+
+use std::mem::size_of;
+
+/// Number of bytes processed at once by the word-wide kernels
+const WORD_SIZE: usize = size_of::<u64>();
+
+/// Longest repeated key pattern which is built on the stack, rather than
+/// XORing the buffer with the key one key's length at a time
+const MAX_PATTERN_SIZE: usize = 256;
+
+/// XORs every byte of the buffer with the single byte key
+pub fn xor_byte_in_place(buf: &mut [u8], key: u8) {
+    let key_word = u64::from_ne_bytes([key; WORD_SIZE]);
+
+    let mut words = buf.chunks_exact_mut(WORD_SIZE);
+    for word in &mut words {
+        let xored = u64::from_ne_bytes((&*word).try_into().unwrap()) ^ key_word;
+        word.copy_from_slice(&xored.to_ne_bytes());
+    }
+
+    for byte in words.into_remainder() {
+        *byte ^= key;
+    }
+}
+
+/// XORs the buffer with the repeating key
+///
+/// An empty key leaves the buffer unchanged
+pub fn xor_in_place(buf: &mut [u8], key: &[u8]) {
+    xor_in_place_with_offset(buf, key, 0);
+}
+
+/// XORs the buffer with the repeating key, starting from the key byte at key_offset
+///
+/// This is used to carry on through the key when a message is XORed in
+/// pieces. An empty key leaves the buffer unchanged
+pub fn xor_in_place_with_offset(buf: &mut [u8], key: &[u8], key_offset: usize) {
+    match key.len() {
+        0 => return,
+        1 => {
+            xor_byte_in_place(buf, key[0]);
+            return;
+        }
+        _ => (),
+    }
+
+    let key_offset = key_offset % key.len();
+
+    /*
+     * The key lines up with the words again after the lowest common multiple of
+     * the key length and word size. When that is short, expand the key to that
+     * length on the stack and XOR the buffer with it a word at a time
+     */
+    let period = lowest_common_multiple(key.len(), WORD_SIZE);
+    if period <= MAX_PATTERN_SIZE {
+        /* For short buffers, expanding the key would cost more than it saves */
+        if buf.len() < period {
+            let key_cycle = key.iter().cycle().skip(key_offset);
+            for (byte, key_byte) in buf.iter_mut().zip(key_cycle) {
+                *byte ^= key_byte;
+            }
+            return;
+        }
+
+        let mut pattern = [0; MAX_PATTERN_SIZE];
+        for (pattern_byte, key_byte) in pattern[..period]
+            .iter_mut()
+            .zip(key.iter().cycle().skip(key_offset))
+        {
+            *pattern_byte = *key_byte;
+        }
+
+        for chunk in buf.chunks_mut(period) {
+            xor_buffers_in_place(chunk, &pattern[..period]);
+        }
+        return;
+    }
+
+    /* A longer key spans many words already, so XOR the buffer with the key itself, one key's length at a time */
+    let (head, rest) = buf.split_at_mut(buf.len().min(key.len() - key_offset));
+    xor_buffers_in_place(head, &key[key_offset..]);
+    for chunk in rest.chunks_mut(key.len()) {
+        xor_buffers_in_place(chunk, key);
+    }
+}
+
+/// XORs src into dst, a word at a time
+///
+/// Only the first min(dst.len(), src.len()) bytes of dst are changed
+pub fn xor_buffers_in_place(dst: &mut [u8], src: &[u8]) {
+    let len = dst.len().min(src.len());
+    let (dst, src) = (&mut dst[..len], &src[..len]);
+
+    let mut dst_words = dst.chunks_exact_mut(WORD_SIZE);
+    let mut src_words = src.chunks_exact(WORD_SIZE);
+    for (dst_word, src_word) in (&mut dst_words).zip(&mut src_words) {
+        let xored = u64::from_ne_bytes((&*dst_word).try_into().unwrap())
+            ^ u64::from_ne_bytes(src_word.try_into().unwrap());
+        dst_word.copy_from_slice(&xored.to_ne_bytes());
+    }
+
+    for (dst_byte, src_byte) in dst_words
+        .into_remainder()
+        .iter_mut()
+        .zip(src_words.remainder())
+    {
+        *dst_byte ^= src_byte;
+    }
+}
+
+/// Returns the lowest common multiple of two non-zero numbers
+fn lowest_common_multiple(a: usize, b: usize) -> usize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// XORs a byte at a time, to check the word-wide kernels against
+    fn naive_xor(buf: &[u8], key: &[u8], key_offset: usize) -> Vec<u8> {
+        buf.iter()
+            .enumerate()
+            .map(|(idx, byte)| byte ^ key[(idx + key_offset) % key.len()])
+            .collect()
+    }
+
+    #[test]
+    fn xor_in_place_matches_naive() {
+        let buf: Vec<u8> = (0..200).map(|i| (i * 37 + 11) as u8).collect();
+        let key: Vec<u8> = (0..100).map(|i| (i * 91 + 3) as u8).collect();
+
+        /* Cover keys shorter than, equal to and longer than a word, and unaligned lengths */
+        for key_len in [1, 2, 3, 5, 8, 13, 16, 29, 31, 33, 40, 64, 100] {
+            for buf_len in [0, 1, 7, 8, 9, 63, 64, 65, 200] {
+                for key_offset in [0, 1, key_len - 1, key_len + 2] {
+                    let mut result = buf[..buf_len].to_vec();
+                    xor_in_place_with_offset(&mut result, &key[..key_len], key_offset);
+                    assert_eq!(
+                        result,
+                        naive_xor(&buf[..buf_len], &key[..key_len], key_offset)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn xor_byte_in_place_matches_naive() {
+        for len in [0, 1, 7, 8, 9, 100] {
+            let mut result: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let expected = naive_xor(&result, &[0xa5], 0);
+            xor_byte_in_place(&mut result, 0xa5);
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn xor_buffers_in_place_shortest() {
+        let mut dst = vec![0xff; 19];
+        xor_buffers_in_place(&mut dst, &[0x0f; 11]);
+
+        let mut expected = vec![0xf0; 11];
+        expected.extend_from_slice(&[0xff; 8]);
+        assert_eq!(dst, expected);
+    }
+
+    #[test]
+    fn xor_in_place_empty_key() {
+        let mut buf = b"unchanged".to_vec();
+        xor_in_place(&mut buf, &[]);
+        assert_eq!(buf, b"unchanged");
+    }
+}
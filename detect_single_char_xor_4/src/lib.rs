@@ -2,24 +2,49 @@
 //! which has been XOR-encoded, and finds the most likely
 //! key and decoded message based off character frequency analysis
 
+use crypto_utilities::parallel::parallel_map;
 use single_xor_cipher_3::{get_file_character_percentages, single_xor_hex_cipher_crack};
 use std::{
+    cmp::max,
     fs::File,
     io::{BufRead, BufReader},
 };
 
+pub mod scanner;
+
+pub use scanner::{
+    scan_file, scan_lines, LineCandidate, LineEncoding, MalformedLine, RepeatingKeyLine,
+    ScanOptions, ScanReport,
+};
+
+/// Number of lines each thread cracks per batch when scanning in parallel
+const LINES_PER_THREAD: usize = 256;
+
 /// This function iterates over the encoded hex strings in the given file,
 /// attempts to find the single byte XOR key for each line, and finds the
 /// decoded message with the most similar character frequencies to the reference
 /// file, and returns it
 pub fn get_decoded_msg(filename: &str, reference_file: &str) -> Result<(u8, String, f32), String> {
+    get_decoded_msg_parallel(filename, reference_file, 1)
+}
+
+/// Returns the same decoded message as get_decoded_msg, cracking the
+/// lines of the file on up to no_of_threads threads
+///
+/// Lines are read and cracked in batches, so memory use does not grow with
+/// the size of the file, and the result does not depend on the number of threads
+pub fn get_decoded_msg_parallel(
+    filename: &str,
+    reference_file: &str,
+    no_of_threads: usize,
+) -> Result<(u8, String, f32), String> {
     /* Get file of encoded hex strings */
     let encoded_hex_file = match File::open(filename) {
         Ok(file) => file,
         Err(e) => return Err(e.to_string()),
     };
 
-    let buf_reader = BufReader::new(encoded_hex_file);
+    let mut lines = BufReader::new(encoded_hex_file).lines();
 
     /* Keep track of key, message and chi which have most similar character frequencies to the sample text */
     let mut smallest_chi = None;
@@ -32,25 +57,42 @@ pub fn get_decoded_msg(filename: &str, reference_file: &str) -> Result<(u8, Stri
         Err(e) => return Err(e.to_string()),
     };
 
-    /* Iterate through encoded hex strings, and try and decode them */
-    for line in buf_reader.lines() {
-        let line = match line {
-            Ok(line) => line,
-            Err(e) => return Err(e.to_string()),
-        };
-        /* Attempt to find the single byte XOR key for this hex string */
-        let cipher_crack_result = single_xor_hex_cipher_crack(line.trim(), &reference_percentages);
-        let (candidate_key, candidate_decoded_msg, candidate_chi) = match cipher_crack_result {
-            Ok((key, decoded_msg, chi)) => (key, decoded_msg, chi),
-            Err(_) => continue,
-            /* If decoding was not successful, continue */
-        };
-
-        /* If this is the solution with the most similar character frequencies to the reference text, record it */
-        if smallest_chi.is_none() || candidate_chi < smallest_chi.unwrap() {
-            smallest_chi = Some(candidate_chi);
-            decoded_message = Some(candidate_decoded_msg);
-            best_key = Some(candidate_key);
+    let batch_size = LINES_PER_THREAD * max(1, no_of_threads);
+    let mut batch = Vec::with_capacity(batch_size);
+
+    loop {
+        /* Read the next batch of encoded hex strings */
+        batch.clear();
+        for line in lines.by_ref().take(batch_size) {
+            match line {
+                Ok(line) => batch.push(line),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        if batch.is_empty() {
+            break;
+        }
+
+        /* Attempt to find the single byte XOR key for each hex string in the batch */
+        let cipher_crack_results = parallel_map(&batch, no_of_threads, |line| {
+            single_xor_hex_cipher_crack(line.trim(), &reference_percentages)
+        });
+
+        /* Consider the results in line order, so ties are broken the same way as a sequential scan */
+        for cipher_crack_result in cipher_crack_results {
+            let (candidate_key, candidate_decoded_msg, candidate_chi) = match cipher_crack_result {
+                Ok((key, decoded_msg, chi)) => (key, decoded_msg, chi),
+                Err(_) => continue,
+                /* If decoding was not successful, continue */
+            };
+
+            /* If this is the solution with the most similar character frequencies to the reference text, record it */
+            if smallest_chi.is_none() || candidate_chi < smallest_chi.unwrap() {
+                smallest_chi = Some(candidate_chi);
+                decoded_message = Some(candidate_decoded_msg);
+                best_key = Some(candidate_key);
+            }
         }
     }
 
@@ -78,4 +120,17 @@ mod tests {
         assert_eq!(decoded_msg, "Now that the party is jumping\n");
         assert_eq!(chi as i32, 258);
     }
+
+    #[test]
+    fn get_decoded_msg_parallel_test() {
+        let expected = get_decoded_msg("hex_strings.txt", "sample-text.txt");
+        assert!(expected.is_ok());
+
+        /* The result must not depend on the number of threads */
+        for no_of_threads in [2, 3, 8] {
+            let result =
+                get_decoded_msg_parallel("hex_strings.txt", "sample-text.txt", no_of_threads);
+            assert_eq!(result, expected);
+        }
+    }
 }
@@ -0,0 +1,443 @@
+//! This module scans files of encoded lines of any size for the lines
+//! which are most likely to have been XOR encoded with a single byte
+//!
+//! Lines are read and cracked in batches, and only the best lines seen so far
+//! are kept, so memory use does not grow with the size of the file. Lines which
+//! cannot be decoded are reported rather than dropped, and lines which look like
+//! they were encoded with a repeating key are flagged for closer inspection
+
+use std::{
+    cmp::{max, Ordering},
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufRead, BufReader},
+    str,
+};
+
+use crypto_utilities::{
+    analysis::get_index_of_coincidence, base64_to_binary_buf, hex_to_binary_buffer, is_valid_hex,
+    parallel::parallel_map,
+};
+use single_xor_cipher_3::{
+    get_file_character_percentages, single_xor_cipher_crack_with_scorer, ChiSquaredScorer,
+    PlaintextScorer,
+};
+
+use crate::LINES_PER_THREAD;
+
+/// Index of coincidence above which the bytes are taken to be plaintext encoded
+/// with a single byte. Random bytes have an index of coincidence of about 1/256,
+/// while English text has about 0.06
+const PLAINTEXT_INDEX_OF_COINCIDENCE: f64 = 0.045;
+
+/// How many times the whole line's index of coincidence a key size's columns must reach.
+/// Keys made of similar bytes leave some structure in every key size, so an absolute
+/// threshold alone would also flag sizes which are not multiples of the key size
+const MIN_COLUMN_IOC_RATIO: f64 = 1.5;
+
+/// Fewest bytes in each column for a key size to be tested for a repeating key,
+/// since shorter columns give too noisy an index of coincidence
+const MIN_COLUMN_LEN: usize = 8;
+
+/// How the lines of a file are encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEncoding {
+    /// Every line is hexadecimal
+    Hex,
+    /// Every line is base64
+    Base64,
+    /// Lines which are valid hexadecimal are decoded as such, and the rest as base64
+    #[default]
+    Auto,
+}
+
+/// Settings for scanning a file of encoded lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Number of best lines to return
+    pub no_of_results: usize,
+    /// How the lines are encoded
+    pub encoding: LineEncoding,
+    /// Most malformed and repeating key lines to return, although every one is counted
+    pub max_reported_lines: usize,
+    /// Largest repeating key size to look for
+    pub max_key_size: usize,
+    /// Number of threads to crack the lines on
+    pub no_of_threads: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            no_of_results: 10,
+            encoding: LineEncoding::Auto,
+            max_reported_lines: 100,
+            max_key_size: 40,
+            no_of_threads: 1,
+        }
+    }
+}
+
+/// A line which decodes under a single byte key
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineCandidate {
+    /// Line number in the file, starting from 1
+    pub line_number: usize,
+    /// The most likely key for the line
+    pub key: u8,
+    /// The line decoded with the key
+    pub decoded: Vec<u8>,
+    /// The scorer's judgement of the decoded line (lower is better)
+    pub score: f32,
+}
+
+/// A line which could not be decoded from the expected encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MalformedLine {
+    /// Line number in the file, starting from 1
+    pub line_number: usize,
+    /// Why the line could not be decoded
+    pub reason: String,
+}
+
+/// A line whose bytes look like plaintext encoded with a repeating key
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepeatingKeyLine {
+    /// Line number in the file, starting from 1
+    pub line_number: usize,
+    /// The most likely key size
+    pub key_size: usize,
+    /// Average index of coincidence of the columns for that key size
+    pub index_of_coincidence: f64,
+}
+
+/// The result of scanning a file of encoded lines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanReport {
+    /// The most likely single byte XOR lines, best first
+    pub top_lines: Vec<LineCandidate>,
+    /// The first malformed lines, in line order
+    pub malformed_lines: Vec<MalformedLine>,
+    /// Total number of malformed lines
+    pub malformed_line_count: usize,
+    /// The first lines which look like repeating key XOR, in line order
+    pub repeating_key_lines: Vec<RepeatingKeyLine>,
+    /// Total number of lines which look like repeating key XOR
+    pub repeating_key_line_count: usize,
+    /// Total number of lines read, including blank lines
+    pub lines_scanned: usize,
+}
+
+/// What was found on a single line
+enum LineOutcome {
+    Blank,
+    Malformed(MalformedLine),
+    Decoded {
+        candidate: Option<LineCandidate>,
+        repeating_key: Option<RepeatingKeyLine>,
+    },
+}
+
+/// Orders candidates so that the worst is at the top of a BinaryHeap
+struct RankedCandidate(LineCandidate);
+
+impl Ord for RankedCandidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        /* A lower score is better, and ties go to the earlier line */
+        self.0
+            .score
+            .total_cmp(&other.0.score)
+            .then(self.0.line_number.cmp(&other.0.line_number))
+    }
+}
+
+impl PartialOrd for RankedCandidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RankedCandidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RankedCandidate {}
+
+/// Scans the file of encoded lines, comparing decoded lines to the character
+/// frequencies of the reference file
+pub fn scan_file(
+    filename: &str,
+    reference_file: &str,
+    options: &ScanOptions,
+) -> Result<ScanReport, String> {
+    let file = File::open(filename).map_err(|e| e.to_string())?;
+
+    /* Get character frequencies from reference file */
+    let reference_percentages = match get_file_character_percentages(reference_file) {
+        Ok(reference_percentages) => reference_percentages,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    scan_lines(
+        BufReader::new(file),
+        &ChiSquaredScorer::new(&reference_percentages),
+        options,
+    )
+}
+
+/// Scans the encoded lines read from the reader, judging decoded lines with the scorer
+///
+/// Lines which are not valid UTF-8 are reported as malformed, but an error
+/// reading from the reader stops the scan. The report does not depend on the number of threads
+pub fn scan_lines<R: BufRead>(
+    mut reader: R,
+    scorer: &dyn PlaintextScorer,
+    options: &ScanOptions,
+) -> Result<ScanReport, String> {
+    let mut report = ScanReport::default();
+    let mut top_lines = BinaryHeap::with_capacity(options.no_of_results + 1);
+
+    let batch_size = LINES_PER_THREAD * max(1, options.no_of_threads);
+    let mut batch: Vec<(usize, Vec<u8>)> = Vec::with_capacity(batch_size);
+    let mut reached_end = false;
+
+    while !reached_end {
+        /* Read the next batch of lines as bytes, so lines which are not UTF-8 can be reported */
+        batch.clear();
+        while batch.len() < batch_size {
+            let mut line = Vec::new();
+            if reader
+                .read_until(b'\n', &mut line)
+                .map_err(|e| e.to_string())?
+                == 0
+            {
+                reached_end = true;
+                break;
+            }
+
+            report.lines_scanned += 1;
+            batch.push((report.lines_scanned, line));
+        }
+
+        let outcomes = parallel_map(&batch, options.no_of_threads, |(line_number, line)| {
+            analyse_line(*line_number, line, scorer, options)
+        });
+
+        /* Consider the outcomes in line order, so the report is the same as a sequential scan */
+        for outcome in outcomes {
+            match outcome {
+                LineOutcome::Blank => (),
+                LineOutcome::Malformed(malformed_line) => {
+                    report.malformed_line_count += 1;
+                    if report.malformed_lines.len() < options.max_reported_lines {
+                        report.malformed_lines.push(malformed_line);
+                    }
+                }
+                LineOutcome::Decoded {
+                    candidate,
+                    repeating_key,
+                } => {
+                    if let Some(candidate) = candidate {
+                        /* Only keep the best lines seen so far, dropping the worst */
+                        top_lines.push(RankedCandidate(candidate));
+                        if top_lines.len() > options.no_of_results {
+                            top_lines.pop();
+                        }
+                    }
+
+                    if let Some(repeating_key) = repeating_key {
+                        report.repeating_key_line_count += 1;
+                        if report.repeating_key_lines.len() < options.max_reported_lines {
+                            report.repeating_key_lines.push(repeating_key);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    report.top_lines = top_lines
+        .into_sorted_vec()
+        .into_iter()
+        .map(|ranked| ranked.0)
+        .collect();
+
+    Ok(report)
+}
+
+/// Decodes a single line, cracks it as single byte XOR, and checks it for a repeating key
+fn analyse_line(
+    line_number: usize,
+    line: &[u8],
+    scorer: &dyn PlaintextScorer,
+    options: &ScanOptions,
+) -> LineOutcome {
+    let line = match str::from_utf8(line) {
+        Ok(line) => line.trim(),
+        Err(e) => {
+            return LineOutcome::Malformed(MalformedLine {
+                line_number,
+                reason: e.to_string(),
+            })
+        }
+    };
+
+    if line.is_empty() {
+        return LineOutcome::Blank;
+    }
+
+    let encoded = match decode_line(line, options.encoding) {
+        Ok(encoded) => encoded,
+        Err(reason) => {
+            return LineOutcome::Malformed(MalformedLine {
+                line_number,
+                reason,
+            })
+        }
+    };
+
+    let candidate = single_xor_cipher_crack_with_scorer(&encoded, scorer)
+        .ok()
+        .map(|(key, decoded, score)| LineCandidate {
+            line_number,
+            key,
+            decoded,
+            score,
+        });
+
+    let repeating_key =
+        get_repeating_key_evidence(&encoded, options.max_key_size).map(|(key_size, ioc)| {
+            RepeatingKeyLine {
+                line_number,
+                key_size,
+                index_of_coincidence: ioc,
+            }
+        });
+
+    LineOutcome::Decoded {
+        candidate,
+        repeating_key,
+    }
+}
+
+/// Decodes a line from the given encoding
+fn decode_line(line: &str, encoding: LineEncoding) -> Result<Vec<u8>, String> {
+    let is_hex = line.len().is_multiple_of(2) && is_valid_hex(line);
+
+    match encoding {
+        LineEncoding::Hex if !is_hex => Err(String::from(
+            "Line is not an even number of hexadecimal digits",
+        )),
+        LineEncoding::Hex => hex_to_binary_buffer(line),
+        LineEncoding::Auto if is_hex => hex_to_binary_buffer(line),
+        LineEncoding::Base64 | LineEncoding::Auto => base64_to_binary_buf(line),
+    }
+}
+
+/// Returns the key size and average column index of coincidence if the bytes look like
+/// plaintext encoded with a repeating key of more than one byte, or None otherwise
+///
+/// Bytes encoded with a single byte key already have a plaintext-like index of coincidence,
+/// so only bytes which do not, but whose columns for some key size do, are flagged
+fn get_repeating_key_evidence(encoded: &[u8], max_key_size: usize) -> Option<(usize, f64)> {
+    let line_ioc = get_index_of_coincidence(encoded);
+    if line_ioc >= PLAINTEXT_INDEX_OF_COINCIDENCE {
+        return None;
+    }
+    let min_column_ioc = PLAINTEXT_INDEX_OF_COINCIDENCE.max(MIN_COLUMN_IOC_RATIO * line_ioc);
+
+    let max_key_size = max_key_size.min(encoded.len() / MIN_COLUMN_LEN);
+    /* Multiples of the key size score about as well, so report the smallest size which qualifies */
+    (2..=max_key_size)
+        .map(|key_size| {
+            let total: f64 = (0..key_size)
+                .map(|column| {
+                    let column: Vec<u8> = encoded
+                        .iter()
+                        .skip(column)
+                        .step_by(key_size)
+                        .copied()
+                        .collect();
+                    get_index_of_coincidence(&column)
+                })
+                .sum();
+            (key_size, total / key_size as f64)
+        })
+        .find(|&(_, ioc)| ioc >= min_column_ioc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_mixed_lines() {
+        let options = ScanOptions {
+            no_of_results: 2,
+            ..ScanOptions::default()
+        };
+        let result = scan_file("mixed_lines.txt", "sample-text.txt", &options);
+        assert!(result.is_ok());
+
+        let report = result.unwrap();
+        assert_eq!(report.lines_scanned, 8);
+        assert_eq!(report.top_lines.len(), 2);
+
+        let best: Vec<(usize, u8)> = report
+            .top_lines
+            .iter()
+            .map(|candidate| (candidate.line_number, candidate.key))
+            .collect();
+        assert_eq!(best, [(2, 42), (4, 67)]);
+        assert_eq!(
+            report.top_lines[0].decoded,
+            b"It was the best of times, it was the worst of times, it was the age of wisdom"
+        );
+
+        /* Both the line which is neither hex nor base64 and the line which is not UTF-8 are reported */
+        let malformed: Vec<usize> = report
+            .malformed_lines
+            .iter()
+            .map(|malformed_line| malformed_line.line_number)
+            .collect();
+        assert_eq!(malformed, [3, 6]);
+        assert_eq!(report.malformed_line_count, 2);
+
+        /* The repeating key line decodes to gibberish as single byte XOR, but is flagged */
+        assert_eq!(report.repeating_key_line_count, 1);
+        assert_eq!(report.repeating_key_lines[0].line_number, 4);
+        assert_eq!(report.repeating_key_lines[0].key_size, 3);
+    }
+
+    #[test]
+    fn scan_options() {
+        /* Base64 lines are malformed when every line should be hex */
+        let options = ScanOptions {
+            encoding: LineEncoding::Hex,
+            max_reported_lines: 1,
+            ..ScanOptions::default()
+        };
+        let report = scan_file("mixed_lines.txt", "sample-text.txt", &options).unwrap();
+        assert_eq!(report.malformed_line_count, 3);
+        assert_eq!(report.malformed_lines.len(), 1);
+        assert_eq!(report.malformed_lines[0].line_number, 2);
+
+        /* The report must not depend on the number of threads */
+        let expected = scan_file(
+            "hex_strings.txt",
+            "sample-text.txt",
+            &ScanOptions::default(),
+        );
+        assert!(expected.is_ok());
+        for no_of_threads in [2, 3] {
+            let options = ScanOptions {
+                no_of_threads,
+                ..ScanOptions::default()
+            };
+            let result = scan_file("hex_strings.txt", "sample-text.txt", &options);
+            assert_eq!(result, expected);
+        }
+    }
+}
@@ -1,9 +1,77 @@
 //! Solution to Challenge 2
 //!
 //! This crate calculated the XOR of two fixed length buffers in hexadecimal format
+//!
+//! It also provides a general API for XORing any number of buffers,
+//! each of which may be given in a different encoding
+
+use crypto_utilities::{
+    base64_to_binary_buf, hex_to_binary_buffer, is_valid_hex, xor::xor_buffers_in_place,
+};
+use hex_to_base64_1::base64_buf_to_utf8_string;
+use std::str;
 
-use crypto_utilities::{hex_to_binary_buffer, is_valid_hex};
-use std::iter::zip;
+/// The encodings which buffers can be given in, or returned in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// The bytes themselves
+    Raw,
+    /// Hexadecimal text, two digits per byte
+    Hex,
+    /// Base64 text, with padding
+    Base64,
+}
+
+impl Encoding {
+    /// Decodes data in this encoding into the bytes it represents
+    ///
+    /// Whitespace in hexadecimal and base64 text is ignored, so data split over lines can be decoded
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        if *self == Encoding::Raw {
+            return Ok(data.to_vec());
+        }
+
+        let mut text = match str::from_utf8(data) {
+            Ok(text) => String::from(text),
+            Err(e) => return Err(format!("Encoded text is not valid UTF-8: {}", e)),
+        };
+        text.retain(|c| !c.is_whitespace());
+
+        match self {
+            Encoding::Hex => {
+                if text.len() % 2 != 0 {
+                    return Err(format!(
+                        "Hexadecimal text has an odd number of digits: {}",
+                        text.len()
+                    ));
+                }
+                if !is_valid_hex(&text) {
+                    return Err(String::from("Received hex buffer with invalid characters"));
+                }
+                hex_to_binary_buffer(&text)
+            }
+            _ => base64_to_binary_buf(&text),
+        }
+    }
+
+    /// Encodes the bytes into this encoding
+    pub fn encode(&self, buf: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Raw => Ok(buf.to_vec()),
+            Encoding::Hex => Ok(buffer_to_hex_string(buf)?.into_bytes()),
+            Encoding::Base64 => Ok(base64_buf_to_utf8_string(buf)?.into_bytes()),
+        }
+    }
+}
+
+/// How to XOR buffers which are not all the same length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthPolicy {
+    /// Return an error
+    RequireEqual,
+    /// Only XOR as many bytes as there are in the shortest buffer
+    TruncateToShortest,
+}
 
 /// Returns the XOR of two equal sized hexadecimal buffers
 pub fn hexadecimal_xor(hex_str1: &str, hex_str2: &str) -> Result<Vec<u8>, String> {
@@ -19,25 +87,77 @@ pub fn hexadecimal_xor(hex_str1: &str, hex_str2: &str) -> Result<Vec<u8>, String
         ));
     }
 
-    let hex_buf1 = match hex_to_binary_buffer(hex_str1) {
-        Ok(buf) => buf,
-        Err(err) => return Err(err),
-    };
+    let mut hex_buf1 = hex_to_binary_buffer(hex_str1)?;
 
-    let hex_buf2 = match hex_to_binary_buffer(hex_str2) {
-        Ok(buf) => buf,
-        Err(err) => return Err(err),
+    let hex_buf2 = hex_to_binary_buffer(hex_str2)?;
+
+    /* XOR the second buffer into the first, rather than collecting into a new vector */
+    fixed_xor_in_place(&mut hex_buf1, &hex_buf2)?;
+
+    Ok(hex_buf1)
+}
+
+/// XORs the second buffer into the first, which must be the same length,
+/// a machine word at a time
+pub fn fixed_xor_in_place(buf1: &mut [u8], buf2: &[u8]) -> Result<(), String> {
+    if buf1.len() != buf2.len() {
+        return Err(format!(
+            "Received different length buffers, {} vs {}",
+            buf1.len(),
+            buf2.len()
+        ));
+    }
+
+    xor_buffers_in_place(buf1, buf2);
+
+    Ok(())
+}
+
+/// Returns the XOR of every buffer given, applying the
+/// length policy if they are not all the same length
+pub fn xor_buffers(buffers: &[&[u8]], length_policy: LengthPolicy) -> Result<Vec<u8>, String> {
+    let (first, rest) = match buffers.split_first() {
+        Some(split) => split,
+        None => return Err(String::from("Received no buffers to XOR")),
     };
 
-    let mut result = Vec::new();
+    let shortest = buffers.iter().map(|buf| buf.len()).min().unwrap();
+    let longest = buffers.iter().map(|buf| buf.len()).max().unwrap();
+
+    if length_policy == LengthPolicy::RequireEqual && shortest != longest {
+        return Err(format!(
+            "Received different length buffers, {} vs {}",
+            shortest, longest
+        ));
+    }
 
-    for (operand1, operand2) in zip(hex_buf1, hex_buf2) {
-        result.push(operand1 ^ operand2);
+    /* XOR every other buffer into a copy of the first */
+    let mut result = first[..shortest].to_vec();
+    for buf in rest {
+        xor_buffers_in_place(&mut result, buf);
     }
 
     Ok(result)
 }
 
+/// Decodes each operand from its own encoding, XORs them all together
+/// applying the length policy, and returns the result in the output encoding
+pub fn xor_encoded(
+    operands: &[(&[u8], Encoding)],
+    length_policy: LengthPolicy,
+    output_encoding: Encoding,
+) -> Result<Vec<u8>, String> {
+    let mut buffers = Vec::with_capacity(operands.len());
+    for (data, encoding) in operands {
+        buffers.push(encoding.decode(data)?);
+    }
+
+    let buffer_refs: Vec<&[u8]> = buffers.iter().map(Vec::as_slice).collect();
+    let result = xor_buffers(&buffer_refs, length_policy)?;
+
+    output_encoding.encode(&result)
+}
+
 /// Takes buffer of binary data and converts it to a hexadecimal string
 pub fn buffer_to_hex_string(buf: &[u8]) -> Result<String, String> {
     let mut result = String::new();
@@ -113,4 +233,79 @@ mod tests {
             assert_eq!(hex, decoded_hex.unwrap());
         }
     }
+
+    #[test]
+    fn fixed_xor_in_place_test() {
+        let mut buf1 = hex_to_binary_buffer("1c0111001f010100061a024b53535009181c").unwrap();
+        let buf2 = hex_to_binary_buffer("686974207468652062756c6c277320657965").unwrap();
+
+        assert!(fixed_xor_in_place(&mut buf1, &buf2).is_ok());
+        assert_eq!(buf1, "the kid don't play".as_bytes());
+
+        assert!(fixed_xor_in_place(&mut buf1, &buf2[1..]).is_err());
+    }
+
+    #[test]
+    fn xor_buffers_many() {
+        let buffers: [&[u8]; 3] = [
+            &[0x0f, 0xf0, 0xff],
+            &[0x01, 0x10, 0x11],
+            &[0x80, 0x08, 0x88],
+        ];
+
+        let result = xor_buffers(&buffers, LengthPolicy::RequireEqual);
+        assert_eq!(result, Ok(vec![0x8e, 0xe8, 0x66]));
+
+        assert!(xor_buffers(&[], LengthPolicy::TruncateToShortest).is_err());
+    }
+
+    #[test]
+    fn xor_buffers_length_policies() {
+        let buffers: [&[u8]; 2] = [&[0xff, 0xff, 0xff], &[0x0f, 0xf0]];
+
+        assert!(xor_buffers(&buffers, LengthPolicy::RequireEqual).is_err());
+        assert_eq!(
+            xor_buffers(&buffers, LengthPolicy::TruncateToShortest),
+            Ok(vec![0xf0, 0x0f])
+        );
+    }
+
+    #[test]
+    fn xor_mixed_encodings() {
+        let operands: [(&[u8], Encoding); 2] = [
+            (
+                "1c0111001f010100061a024b53535009181c".as_bytes(),
+                Encoding::Hex,
+            ),
+            ("aGl0IHRoZSBidWxs\nJ3MgZXll".as_bytes(), Encoding::Base64),
+        ];
+
+        let test_data: [(Encoding, &[u8]); 3] = [
+            (
+                Encoding::Hex,
+                "746865206b696420646f6e277420706c6179".as_bytes(),
+            ),
+            (Encoding::Base64, "dGhlIGtpZCBkb24ndCBwbGF5".as_bytes()),
+            (Encoding::Raw, "the kid don't play".as_bytes()),
+        ];
+
+        for (output_encoding, expected) in test_data {
+            let result = xor_encoded(&operands, LengthPolicy::RequireEqual, output_encoding);
+            assert_eq!(result, Ok(expected.to_vec()));
+        }
+    }
+
+    #[test]
+    fn decode_invalid_encodings() {
+        let test_data: [(&[u8], Encoding); 4] = [
+            ("abc".as_bytes(), Encoding::Hex),
+            ("zz".as_bytes(), Encoding::Hex),
+            ("a=".as_bytes(), Encoding::Base64),
+            (&[0xff, 0xfe], Encoding::Base64),
+        ];
+
+        for (data, encoding) in test_data {
+            assert!(encoding.decode(data).is_err());
+        }
+    }
 }
@@ -67,13 +67,13 @@ pub fn base64_buf_to_utf8_string(buf: &[u8]) -> Result<String, String> {
     }
 
     /* Add any leftover parts of the last processed byte */
-    if buf.len() % 3 != 0 {
+    if !buf.len().is_multiple_of(3) {
         let c = base64_u8_to_utf8_char(leftovers)?;
         result.push(c);
     }
 
     /* Add padding on the end */
-    while result.len() % 4 != 0 {
+    while !result.len().is_multiple_of(4) {
         result.push('=');
     }
 
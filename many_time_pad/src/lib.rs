@@ -0,0 +1,324 @@
+//! This crate provides tools for recovering messages which were all
+//! XORed with the same keystream, known as a many-time pad
+//!
+//! XORing two such ciphertexts cancels out the keystream, leaving the XOR
+//! of the two plaintexts. Guessing a word (a crib) in one plaintext and
+//! sliding it along reveals the other plaintext wherever the guess is right.
+//! When there are no good guesses, the two_time_pad module can search for
+//! both plaintexts automatically
+
+use fixed_xor_2::{xor_buffers, LengthPolicy};
+use repeating_key_xor_5::multi_key_xor_encode;
+use single_xor_cipher_3::{NgramModel, PlaintextScorer};
+
+pub mod two_time_pad;
+
+pub use two_time_pad::{
+    recover_two_time_pad, recover_two_time_pad_with_keystream, TwoTimePadSolution,
+};
+
+/// Character shown in partial plaintexts for bytes whose keystream is not yet known
+pub const UNKNOWN_CHAR: char = '_';
+
+/// Character shown in partial plaintexts for recovered bytes which are not printable
+pub const UNPRINTABLE_CHAR: char = '.';
+
+/// The result of placing a crib at one offset of a pair of messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct CribPlacement {
+    /// Offset of the crib in the first message of the pair
+    pub offset: usize,
+    /// The bytes of the second message implied by the crib being at this offset
+    pub fragment: Vec<u8>,
+    /// The scorer's judgement of the fragment (lower is better)
+    pub score: f32,
+}
+
+/// Holds a set of messages XORed with the same keystream, along with the
+/// parts of the keystream which have been recovered so far
+#[derive(Debug, Clone)]
+pub struct CribDragger {
+    ciphertexts: Vec<Vec<u8>>,
+    keystream: Vec<Option<u8>>,
+}
+
+impl CribDragger {
+    /// Returns a dragger for the given ciphertexts, with none of the keystream known
+    pub fn new(ciphertexts: Vec<Vec<u8>>) -> Result<Self, String> {
+        if ciphertexts.len() < 2 {
+            return Err(String::from(
+                "At least two ciphertexts are needed to drag a crib",
+            ));
+        }
+
+        let longest = ciphertexts.iter().map(Vec::len).max().unwrap();
+
+        Ok(Self {
+            ciphertexts,
+            keystream: vec![None; longest],
+        })
+    }
+
+    /// Returns the ciphertexts being recovered
+    pub fn ciphertexts(&self) -> &[Vec<u8>] {
+        &self.ciphertexts
+    }
+
+    /// Returns the keystream, with None for the bytes which are not yet known
+    pub fn keystream(&self) -> &[Option<u8>] {
+        &self.keystream
+    }
+
+    /// Returns the XOR of two of the ciphertexts, which is the XOR of their plaintexts,
+    /// truncated to the length of the shorter message
+    pub fn xor_pair(&self, first: usize, second: usize) -> Result<Vec<u8>, String> {
+        let first = self.ciphertext(first)?;
+        let second = self.ciphertext(second)?;
+
+        xor_buffers(&[first, second], LengthPolicy::TruncateToShortest)
+    }
+
+    /// Slides the crib along the first message of the pair, and returns the fragment
+    /// of the second message implied at each offset, ranked by the scorer
+    ///
+    /// Offsets whose fragment the scorer rejects are not returned, and
+    /// ties in the score are broken by the smaller offset
+    pub fn drag_crib(
+        &self,
+        first: usize,
+        second: usize,
+        crib: &[u8],
+        scorer: &dyn PlaintextScorer,
+    ) -> Result<Vec<CribPlacement>, String> {
+        if crib.is_empty() {
+            return Err(String::from("Cannot drag an empty crib"));
+        }
+
+        let pair_xor = self.xor_pair(first, second)?;
+        let mut placements = Vec::new();
+
+        /* If the crib is at this offset in the first message, XORing it out leaves the second */
+        for (offset, window) in pair_xor.windows(crib.len()).enumerate() {
+            let fragment = multi_key_xor_encode(window, crib);
+
+            if let Some(score) = scorer.score(&fragment) {
+                placements.push(CribPlacement {
+                    offset,
+                    fragment,
+                    score,
+                });
+            }
+        }
+
+        placements.sort_by(|a, b| a.score.total_cmp(&b.score));
+
+        Ok(placements)
+    }
+
+    /// Records that the crib is the plaintext of the message at the given
+    /// offset, locking in the keystream bytes which it implies
+    pub fn lock_crib(&mut self, message: usize, offset: usize, crib: &[u8]) -> Result<(), String> {
+        let ciphertext = self.ciphertext(message)?;
+        let end = match offset.checked_add(crib.len()) {
+            Some(end) if end <= ciphertext.len() => end,
+            _ => {
+                return Err(format!(
+                    "Crib of {} bytes at offset {} runs past the end of message {}",
+                    crib.len(),
+                    offset,
+                    message
+                ))
+            }
+        };
+
+        /* The keystream is the ciphertext XORed with the plaintext */
+        let fragment = multi_key_xor_encode(&ciphertext[offset..end], crib);
+        self.lock_keystream(offset, &fragment)
+    }
+
+    /// Locks in a fragment of the keystream starting at the given offset
+    pub fn lock_keystream(&mut self, offset: usize, fragment: &[u8]) -> Result<(), String> {
+        if offset
+            .checked_add(fragment.len())
+            .is_none_or(|end| end > self.keystream.len())
+        {
+            return Err(format!(
+                "Keystream fragment of {} bytes at offset {} runs past the end of the longest message",
+                fragment.len(),
+                offset
+            ));
+        }
+
+        for (known, &byte) in self.keystream[offset..].iter_mut().zip(fragment) {
+            *known = Some(byte);
+        }
+
+        Ok(())
+    }
+
+    /// Searches for the plaintexts of a pair of messages using the model,
+    /// keeping to the keystream which has been locked in so far
+    ///
+    /// The solution is not locked in, so it can be checked first and then
+    /// passed to lock_keystream in whole or in part
+    pub fn recover_pair(
+        &self,
+        first: usize,
+        second: usize,
+        model: &NgramModel,
+        beam_width: usize,
+    ) -> Result<TwoTimePadSolution, String> {
+        recover_two_time_pad_with_keystream(
+            self.ciphertext(first)?,
+            self.ciphertext(second)?,
+            &self.keystream,
+            model,
+            beam_width,
+        )
+    }
+
+    /// Forgets len bytes of the keystream starting at the given offset,
+    /// for when a crib turns out to have been placed wrongly
+    pub fn unlock_keystream(&mut self, offset: usize, len: usize) {
+        let end = offset.saturating_add(len).min(self.keystream.len());
+        for known in self.keystream.iter_mut().take(end).skip(offset) {
+            *known = None;
+        }
+    }
+
+    /// Returns the plaintext of the message as far as the keystream is known,
+    /// with None for the bytes which are not yet known
+    pub fn partial_plaintext(&self, message: usize) -> Result<Vec<Option<u8>>, String> {
+        let ciphertext = self.ciphertext(message)?;
+
+        Ok(ciphertext
+            .iter()
+            .zip(&self.keystream)
+            .map(|(byte, known)| known.map(|key_byte| byte ^ key_byte))
+            .collect())
+    }
+
+    /// Returns the partial plaintext of every message
+    pub fn partial_plaintexts(&self) -> Vec<Vec<Option<u8>>> {
+        (0..self.ciphertexts.len())
+            .map(|message| self.partial_plaintext(message).unwrap())
+            .collect()
+    }
+
+    /// Returns the partial plaintext of the message as a printable string,
+    /// showing unknown bytes as UNKNOWN_CHAR and unprintable bytes as UNPRINTABLE_CHAR
+    pub fn render_partial_plaintext(&self, message: usize) -> Result<String, String> {
+        Ok(self
+            .partial_plaintext(message)?
+            .into_iter()
+            .map(|byte| match byte {
+                Some(byte) if byte.is_ascii_graphic() || byte == b' ' => byte as char,
+                Some(_) => UNPRINTABLE_CHAR,
+                None => UNKNOWN_CHAR,
+            })
+            .collect())
+    }
+
+    /// Returns the ciphertext of the message, or an error if there is no such message
+    fn ciphertext(&self, message: usize) -> Result<&[u8], String> {
+        match self.ciphertexts.get(message) {
+            Some(ciphertext) => Ok(ciphertext),
+            None => Err(format!(
+                "There is no message {}, only {} were given",
+                message,
+                self.ciphertexts.len()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use single_xor_cipher_3::{get_file_character_percentages, ChiSquaredScorer};
+
+    const PLAINTEXTS: [&str; 3] = [
+        "Meet me at the old bridge at midnight",
+        "Send more money before the end of the week",
+        "The package arrives on the morning train",
+    ];
+
+    /// Encrypts every plaintext with the same keystream
+    fn encrypt_plaintexts() -> Vec<Vec<u8>> {
+        let keystream: Vec<u8> = (0..64u32).map(|i| (i * 167 + 29) as u8).collect();
+
+        PLAINTEXTS
+            .iter()
+            .map(|plaintext| {
+                multi_key_xor_encode(plaintext.as_bytes(), &keystream[..plaintext.len()])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn xor_pair_is_xor_of_plaintexts() {
+        let dragger = CribDragger::new(encrypt_plaintexts()).unwrap();
+
+        let result = dragger.xor_pair(0, 1);
+        let expected = xor_buffers(
+            &[PLAINTEXTS[0].as_bytes(), PLAINTEXTS[1].as_bytes()],
+            LengthPolicy::TruncateToShortest,
+        );
+        assert_eq!(result, expected);
+
+        assert!(dragger.xor_pair(0, 3).is_err());
+    }
+
+    #[test]
+    fn drag_crib_reveals_other_message() {
+        let reference_percentages = get_file_character_percentages("reference-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+        let dragger = CribDragger::new(encrypt_plaintexts()).unwrap();
+
+        /* " at the old bridge" is at offset 7 of the first message */
+        let result = dragger.drag_crib(0, 1, b" at the old bridge", &scorer);
+        assert!(result.is_ok());
+
+        let placements = result.unwrap();
+        let correct = placements
+            .iter()
+            .position(|placement| placement.offset == 7);
+        assert!(correct.is_some());
+        assert_eq!(correct, Some(0));
+        assert_eq!(placements[0].fragment, b"re money before th");
+
+        /* Placements are ranked in ascending score order */
+        for pair in placements.windows(2) {
+            assert!(pair[0].score <= pair[1].score);
+        }
+    }
+
+    #[test]
+    fn lock_crib_updates_every_message() {
+        let mut dragger = CribDragger::new(encrypt_plaintexts()).unwrap();
+        assert!(dragger.lock_crib(0, 10, b" the ").is_ok());
+
+        let expected = [
+            "__________ the ______________________",
+            "__________money___________________________",
+            "__________e arr_________________________",
+        ];
+        for (message, expected) in expected.iter().enumerate() {
+            assert_eq!(
+                dragger.render_partial_plaintext(message).as_deref(),
+                Ok(&expected[..])
+            );
+        }
+
+        dragger.unlock_keystream(12, usize::MAX);
+        assert_eq!(
+            dragger.render_partial_plaintext(1).unwrap(),
+            "__________mo______________________________"
+        );
+
+        assert!(dragger.lock_crib(0, 35, b"night").is_err());
+        assert!(dragger.lock_crib(0, usize::MAX, b"night").is_err());
+        assert!(dragger.lock_keystream(usize::MAX, b"night").is_err());
+        assert!(CribDragger::new(vec![vec![1, 2, 3]]).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! This module recovers two plaintexts which were XORed with the same
+//! keystream without any cribs, using a beam search scored by an n-gram model
+//!
+//! At each position every plaintext byte for the first message fixes the byte
+//! of the second, so the search extends both plaintexts together. Partial
+//! solutions which end with the same context can only be extended in the
+//! same ways, so only the best of them is kept, as in the Viterbi algorithm
+
+use std::collections::HashSet;
+
+use fixed_xor_2::{xor_buffers, LengthPolicy};
+use single_xor_cipher_3::NgramModel;
+
+/// Returns true if the byte could be part of a plaintext message
+fn is_plaintext_byte(byte: u8) -> bool {
+    byte.is_ascii_graphic() || matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// The most likely pair of plaintexts found for two ciphertexts
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoTimePadSolution {
+    /// The plaintext of the first ciphertext
+    pub first: Vec<u8>,
+    /// The plaintext of the second ciphertext
+    pub second: Vec<u8>,
+    /// The keystream implied by the plaintexts
+    pub keystream: Vec<u8>,
+    /// Negative log likelihood of both plaintexts under the model (lower is better)
+    pub score: f64,
+}
+
+/// A partial solution in the beam
+struct BeamState {
+    first_context: Vec<u8>,
+    second_context: Vec<u8>,
+    score: f64,
+}
+
+/// Recovers two plaintexts XORed with the same keystream, up to the length of the shorter one
+///
+/// Without any known keystream the two plaintexts are interchangeable, so they may be
+/// returned in either order. A wider beam is slower but less likely to lose the answer
+pub fn recover_two_time_pad(
+    first: &[u8],
+    second: &[u8],
+    model: &NgramModel,
+    beam_width: usize,
+) -> Result<TwoTimePadSolution, String> {
+    recover_two_time_pad_with_keystream(first, second, &[], model, beam_width)
+}
+
+/// Recovers two plaintexts XORed with the same keystream, keeping to the keystream
+/// bytes which are already known, such as those found by crib dragging
+///
+/// known_keystream may be shorter than the ciphertexts, in which case the rest is unknown
+pub fn recover_two_time_pad_with_keystream(
+    first: &[u8],
+    second: &[u8],
+    known_keystream: &[Option<u8>],
+    model: &NgramModel,
+    beam_width: usize,
+) -> Result<TwoTimePadSolution, String> {
+    if beam_width == 0 {
+        return Err(String::from("Beam width must be greater than zero"));
+    }
+
+    let pair_xor = xor_buffers(&[first, second], LengthPolicy::TruncateToShortest)?;
+    let context_len = model.order() - 1;
+
+    let mut beam = vec![BeamState {
+        first_context: Vec::new(),
+        second_context: Vec::new(),
+        score: 0.0,
+    }];
+
+    /* For each position, the state each kept state came from and the byte it added */
+    let mut history: Vec<Vec<(usize, u8)>> = Vec::with_capacity(pair_xor.len());
+
+    for (idx, &xor_byte) in pair_xor.iter().enumerate() {
+        /* A known keystream byte leaves only one choice, otherwise try every plaintext byte */
+        let choices: Vec<u8> = match known_keystream.get(idx).copied().flatten() {
+            Some(key_byte) => vec![first[idx] ^ key_byte],
+            None => (0..=u8::MAX)
+                .filter(|&byte| is_plaintext_byte(byte) && is_plaintext_byte(byte ^ xor_byte))
+                .collect(),
+        };
+
+        let mut candidates = Vec::with_capacity(beam.len() * choices.len());
+        for (parent, state) in beam.iter().enumerate() {
+            for &byte in &choices {
+                let score = state.score
+                    - model.log_probability(&state.first_context, byte)
+                    - model.log_probability(&state.second_context, byte ^ xor_byte);
+                candidates.push((score, parent, byte));
+            }
+        }
+
+        /* Stable sort, so ties go to the earlier state and smaller byte */
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut next_beam = Vec::with_capacity(beam_width);
+        let mut steps = Vec::with_capacity(beam_width);
+        let mut seen_contexts = HashSet::new();
+
+        for (score, parent, byte) in candidates {
+            let first_context = extend_context(&beam[parent].first_context, byte, context_len);
+
+            /* The second context follows from the first, so the first alone identifies the state */
+            if !seen_contexts.insert(first_context.clone()) {
+                continue;
+            }
+
+            next_beam.push(BeamState {
+                first_context,
+                second_context: extend_context(
+                    &beam[parent].second_context,
+                    byte ^ xor_byte,
+                    context_len,
+                ),
+                score,
+            });
+            steps.push((parent, byte));
+
+            if next_beam.len() == beam_width {
+                break;
+            }
+        }
+
+        if next_beam.is_empty() {
+            return Err(format!(
+                "No pair of plaintexts fits the ciphertexts at offset {}",
+                idx
+            ));
+        }
+
+        beam = next_beam;
+        history.push(steps);
+    }
+
+    /* The beam is sorted, so its first state is the best, and is followed back to the start */
+    let mut first_plaintext = vec![0; pair_xor.len()];
+    let mut state = 0;
+    for (idx, steps) in history.iter().enumerate().rev() {
+        let (parent, byte) = steps[state];
+        first_plaintext[idx] = byte;
+        state = parent;
+    }
+
+    let second_plaintext = xor_buffers(&[&first_plaintext, &pair_xor], LengthPolicy::RequireEqual)?;
+    let keystream = xor_buffers(
+        &[&first_plaintext, &first[..pair_xor.len()]],
+        LengthPolicy::RequireEqual,
+    )?;
+
+    Ok(TwoTimePadSolution {
+        first: first_plaintext,
+        second: second_plaintext,
+        keystream,
+        score: beam[0].score,
+    })
+}
+
+/// Appends the byte to the context, keeping only the last context_len bytes
+fn extend_context(context: &[u8], byte: u8, context_len: usize) -> Vec<u8> {
+    let mut extended = Vec::with_capacity(context.len() + 1);
+    extended.extend_from_slice(context);
+    extended.push(byte);
+    extended.split_off(extended.len().saturating_sub(context_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CribDragger;
+    use repeating_key_xor_5::multi_key_xor_encode;
+
+    const FIRST: &[u8] = b"we waited by the river until the sun went down behind the hills";
+    const SECOND: &[u8] = b"the children played in the garden while their mother read";
+
+    fn reference_model() -> NgramModel {
+        NgramModel::from_files(&["reference-text.txt"], 5).unwrap()
+    }
+
+    /// Encrypts both plaintexts with the same keystream
+    fn encrypt_pair() -> (Vec<u8>, Vec<u8>) {
+        let keystream: Vec<u8> = (0..64u32).map(|i| (i * 211 + 71) as u8).collect();
+
+        (
+            multi_key_xor_encode(FIRST, &keystream[..FIRST.len()]),
+            multi_key_xor_encode(SECOND, &keystream[..SECOND.len()]),
+        )
+    }
+
+    #[test]
+    fn recover_two_plaintexts() {
+        let (first, second) = encrypt_pair();
+
+        let result = recover_two_time_pad(&first, &second, &reference_model(), 50);
+        assert!(result.is_ok());
+
+        /* Neither plaintext is in the reference text, so the model has to generalise to find them */
+        let solution = result.unwrap();
+        assert_eq!(solution.first, &FIRST[..SECOND.len()]);
+        assert_eq!(solution.second, SECOND);
+
+        /* The keystream is whatever turns the first ciphertext into the first plaintext */
+        assert_eq!(
+            multi_key_xor_encode(&first[..SECOND.len()], &solution.keystream),
+            solution.first
+        );
+    }
+
+    #[test]
+    fn recover_pair_keeps_locked_keystream() {
+        let (first, second) = encrypt_pair();
+        let mut dragger = CribDragger::new(vec![first, second]).unwrap();
+        dragger.lock_crib(0, 0, b"we waited by").unwrap();
+
+        let result = dragger.recover_pair(0, 1, &reference_model(), 50);
+        assert!(result.is_ok());
+
+        let solution = result.unwrap();
+        assert!(solution.first.starts_with(b"we waited by"));
+        assert!(solution.second.starts_with(b"the children"));
+    }
+
+    #[test]
+    fn recover_two_time_pad_errors() {
+        let model = reference_model();
+        assert!(recover_two_time_pad(b"abc", b"def", &model, 0).is_err());
+
+        /* A known keystream byte is kept even if it gives an unlikely plaintext */
+        let known = [Some(0)];
+        let result = recover_two_time_pad_with_keystream(b"a", b"\x01", &known, &model, 10);
+        assert_eq!(result.map(|solution| solution.second), Ok(vec![1]));
+    }
+}
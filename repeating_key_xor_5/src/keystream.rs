@@ -0,0 +1,301 @@
+//! This module generalises the repeating XOR key to any stream of key bytes,
+//! including streams which depend on the message itself
+//!
+//! A key stream hands out one key byte per message byte, and is then told the
+//! plaintext and ciphertext bytes it produced, so autokey ciphers can feed the
+//! message back into the key. Key streams carry on from where the last call
+//! left off, so a message can be encoded or decoded in pieces
+
+use std::collections::VecDeque;
+
+use crypto_utilities::xor::xor_in_place_with_offset;
+
+/// A source of key bytes for an XOR cipher
+pub trait KeyStream {
+    /// Returns the key byte for the next position in the message
+    fn next_key_byte(&mut self) -> u8;
+
+    /// Tells the stream the plaintext and ciphertext bytes at the position
+    /// which was just keyed. Streams which do not depend on the message ignore this
+    fn feed(&mut self, _plaintext: u8, _ciphertext: u8) {}
+
+    /// XOR encodes the plaintext in place, carrying on through the stream
+    fn encode_in_place(&mut self, msg: &mut [u8]) {
+        for byte in msg {
+            let ciphertext = *byte ^ self.next_key_byte();
+            self.feed(*byte, ciphertext);
+            *byte = ciphertext;
+        }
+    }
+
+    /// XOR decodes the ciphertext in place, carrying on through the stream
+    fn decode_in_place(&mut self, encoded_msg: &mut [u8]) {
+        for byte in encoded_msg {
+            let plaintext = *byte ^ self.next_key_byte();
+            self.feed(plaintext, *byte);
+            *byte = plaintext;
+        }
+    }
+}
+
+/// Return a vector containing the result of XOR encoding the message with the key stream
+pub fn key_stream_encode(msg: &[u8], key_stream: &mut dyn KeyStream) -> Vec<u8> {
+    let mut encoded = msg.to_vec();
+    key_stream.encode_in_place(&mut encoded);
+    encoded
+}
+
+/// Return a vector containing the result of XOR decoding the message with the key stream
+pub fn key_stream_decode(encoded_msg: &[u8], key_stream: &mut dyn KeyStream) -> Vec<u8> {
+    let mut decoded = encoded_msg.to_vec();
+    key_stream.decode_in_place(&mut decoded);
+    decoded
+}
+
+/// A fixed key which is cycled through, as used by multi_key_xor_encode
+///
+/// The stream borrows the key rather than copying it, so it is cheap to make one per message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepeatingKey<'a> {
+    key: &'a [u8],
+    position: usize,
+}
+
+impl<'a> RepeatingKey<'a> {
+    /// Returns a stream which starts from the first byte of the key
+    pub fn new(key: &'a [u8]) -> Result<Self, String> {
+        if key.is_empty() {
+            return Err(String::from("Cannot cycle through an empty key"));
+        }
+
+        Ok(Self { key, position: 0 })
+    }
+}
+
+impl KeyStream for RepeatingKey<'_> {
+    fn next_key_byte(&mut self) -> u8 {
+        let key_byte = self.key[self.position];
+        self.position = (self.position + 1) % self.key.len();
+        key_byte
+    }
+
+    /* The key does not depend on the message, so whole words can be XORed at once */
+    fn encode_in_place(&mut self, msg: &mut [u8]) {
+        xor_in_place_with_offset(msg, self.key, self.position);
+        self.position = (self.position + msg.len()) % self.key.len();
+    }
+
+    fn decode_in_place(&mut self, encoded_msg: &mut [u8]) {
+        self.encode_in_place(encoded_msg);
+    }
+}
+
+/// Which bytes of the message an autokey cipher feeds back into its key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutokeyFeedback {
+    /// Each key byte after the primer is the plaintext byte one primer length earlier
+    Plaintext,
+    /// Each key byte after the primer is the ciphertext byte one primer length earlier
+    Ciphertext,
+}
+
+/// A key which starts with a primer and carries on with the message itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct Autokey {
+    pending: VecDeque<u8>,
+    feedback: AutokeyFeedback,
+}
+
+impl Autokey {
+    /// Returns a stream which uses the primer as the first key bytes
+    pub fn new(primer: &[u8], feedback: AutokeyFeedback) -> Result<Self, String> {
+        if primer.is_empty() {
+            return Err(String::from(
+                "An autokey cipher needs a primer of at least one byte",
+            ));
+        }
+
+        Ok(Self {
+            pending: primer.iter().copied().collect(),
+            feedback,
+        })
+    }
+}
+
+impl KeyStream for Autokey {
+    fn next_key_byte(&mut self) -> u8 {
+        /* Every key byte taken is replaced by a fed back byte, so the queue is never empty */
+        self.pending.pop_front().unwrap()
+    }
+
+    fn feed(&mut self, plaintext: u8, ciphertext: u8) {
+        self.pending.push_back(match self.feedback {
+            AutokeyFeedback::Plaintext => plaintext,
+            AutokeyFeedback::Ciphertext => ciphertext,
+        });
+    }
+}
+
+/// A key byte which starts at a value and has a step added after every message byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncrementingKey {
+    next: u8,
+    step: u8,
+}
+
+impl IncrementingKey {
+    /// Returns a stream which starts at the given key byte, wrapping around after 255
+    pub fn new(start: u8, step: u8) -> Self {
+        Self { next: start, step }
+    }
+}
+
+impl KeyStream for IncrementingKey {
+    fn next_key_byte(&mut self) -> u8 {
+        let key_byte = self.next;
+        self.next = self.next.wrapping_add(self.step);
+        key_byte
+    }
+}
+
+/// A 32 bit Galois linear feedback shift register
+///
+/// Each key byte is made of the next 8 output bits, least significant bit first.
+/// At every step the lowest bit of the state is output and the state is shifted
+/// right, and if that bit was set, the state is XORed with the taps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lfsr {
+    state: u32,
+    taps: u32,
+}
+
+impl Lfsr {
+    /// Returns a stream starting from the seed state, with the given feedback taps
+    pub fn new(seed: u32, taps: u32) -> Result<Self, String> {
+        if seed == 0 {
+            return Err(String::from("An LFSR seeded with zero only outputs zeroes"));
+        }
+        if taps == 0 {
+            return Err(String::from("An LFSR needs at least one tap"));
+        }
+
+        Ok(Self { state: seed, taps })
+    }
+
+    /// Returns the current state of the register
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    /// Steps the register once, returning the output bit
+    fn step(&mut self) -> u8 {
+        let bit = (self.state & 1) as u8;
+        self.state >>= 1;
+        if bit == 1 {
+            self.state ^= self.taps;
+        }
+        bit
+    }
+}
+
+impl KeyStream for Lfsr {
+    fn next_key_byte(&mut self) -> u8 {
+        (0..8).fold(0, |key_byte, bit| key_byte | (self.step() << bit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multi_key_xor_encode;
+
+    const MSG: &[u8] =
+        b"Burning 'em, if you ain't quick and nimble\nI go crazy when I hear a cymbal";
+
+    /// Encodes in uneven pieces, checks the result, then decodes it with a fresh stream
+    fn check_round_trip(make_stream: impl Fn() -> Box<dyn KeyStream>, expected: &[u8]) {
+        let mut stream = make_stream();
+        let mut encoded = MSG.to_vec();
+        for chunk in encoded.chunks_mut(5) {
+            stream.encode_in_place(chunk);
+        }
+        assert_eq!(encoded, expected);
+
+        assert_eq!(key_stream_decode(&encoded, make_stream().as_mut()), MSG);
+    }
+
+    #[test]
+    fn repeating_key_matches_multi_key_xor_encode() {
+        let expected = multi_key_xor_encode(MSG, b"ICE");
+        check_round_trip(|| Box::new(RepeatingKey::new(b"ICE").unwrap()), &expected);
+
+        /* The byte at a time path gives the same key as the word-wide one */
+        let mut stream = RepeatingKey::new(b"ICE").unwrap();
+        let key: Vec<u8> = (0..7).map(|_| stream.next_key_byte()).collect();
+        assert_eq!(key, b"ICEICEI");
+
+        assert!(RepeatingKey::new(b"").is_err());
+    }
+
+    #[test]
+    fn autokey_plaintext_feedback() {
+        let mut expected = multi_key_xor_encode(&MSG[..3], b"key");
+        expected.extend(
+            MSG[3..]
+                .iter()
+                .zip(MSG)
+                .map(|(byte, key_byte)| byte ^ key_byte),
+        );
+
+        check_round_trip(
+            || Box::new(Autokey::new(b"key", AutokeyFeedback::Plaintext).unwrap()),
+            &expected,
+        );
+        assert!(Autokey::new(b"", AutokeyFeedback::Plaintext).is_err());
+    }
+
+    #[test]
+    fn autokey_ciphertext_feedback() {
+        let mut expected = multi_key_xor_encode(&MSG[..3], b"key");
+        for idx in 3..MSG.len() {
+            expected.push(MSG[idx] ^ expected[idx - 3]);
+        }
+
+        check_round_trip(
+            || Box::new(Autokey::new(b"key", AutokeyFeedback::Ciphertext).unwrap()),
+            &expected,
+        );
+    }
+
+    #[test]
+    fn incrementing_key() {
+        let expected: Vec<u8> = MSG
+            .iter()
+            .enumerate()
+            .map(|(idx, byte)| byte ^ (0xf0u8).wrapping_add(3 * idx as u8))
+            .collect();
+
+        check_round_trip(|| Box::new(IncrementingKey::new(0xf0, 3)), &expected);
+    }
+
+    #[test]
+    fn lfsr_key_stream() {
+        /* Taps for the maximal length 16 bit register x^16 + x^14 + x^13 + x^11 + 1 */
+        let mut lfsr = Lfsr::new(0xace1, 0xb400).unwrap();
+        let key: Vec<u8> = (0..4).map(|_| lfsr.next_key_byte()).collect();
+        assert_eq!(key, [0xe1, 0xc4, 0x62, 0x3b]);
+
+        /* A maximal length register returns to its seed after 2^16 - 1 steps */
+        let mut lfsr = Lfsr::new(0xace1, 0xb400).unwrap();
+        for _ in 0..65535 {
+            lfsr.step();
+        }
+        assert_eq!(lfsr.state(), 0xace1);
+
+        let expected = key_stream_encode(MSG, &mut Lfsr::new(0xace1, 0xb400).unwrap());
+        check_round_trip(|| Box::new(Lfsr::new(0xace1, 0xb400).unwrap()), &expected);
+
+        assert!(Lfsr::new(0, 0xb400).is_err());
+        assert!(Lfsr::new(1, 0).is_err());
+    }
+}
@@ -2,6 +2,15 @@
 
 use core::str;
 
+pub mod keystream;
+pub mod stream;
+
+pub use keystream::{
+    key_stream_decode, key_stream_encode, Autokey, AutokeyFeedback, IncrementingKey, KeyStream,
+    Lfsr, RepeatingKey,
+};
+pub use stream::{XorReader, XorWriter};
+
 /// Return a vector containing the result of XOR encoding
 /// the message with the multi byte key
 pub fn multi_key_xor_encode_str(msg: &str, key: &str) -> Vec<u8> {
@@ -11,10 +20,19 @@ pub fn multi_key_xor_encode_str(msg: &str, key: &str) -> Vec<u8> {
 /// Return a vector containing the result of XOR encoding
 /// the buffer with the multi byte key
 pub fn multi_key_xor_encode(msg: &[u8], key: &[u8]) -> Vec<u8> {
-    let mut key_cycle = key.iter().cycle();
-    msg.iter()
-        .map(|msg_byte| msg_byte ^ key_cycle.next().unwrap())
-        .collect()
+    let mut encoded = msg.to_vec();
+    multi_key_xor_encode_in_place(&mut encoded, key);
+    encoded
+}
+
+/// XOR encodes the buffer with the multi byte key in place,
+/// a machine word at a time
+///
+/// An empty key leaves the buffer unchanged
+pub fn multi_key_xor_encode_in_place(msg: &mut [u8], key: &[u8]) {
+    if let Ok(mut key_stream) = RepeatingKey::new(key) {
+        key_stream.encode_in_place(msg);
+    }
 }
 
 #[cfg(test)]
@@ -34,4 +52,19 @@ mod tests {
         let expected_buf = crypto_utilities::hex_to_binary_buffer("0b3637272a2b2e63622c2e69692a23693a2a3c6324202d623d63343c2a26226324272765272a282b2f20430a652e2c652a3124333a653e2b2027630c692b20283165286326302e27282f").unwrap();
         assert_eq!(expected_buf, result);
     }
+
+    #[test]
+    fn multi_key_xor_encode_in_place_test() {
+        let mut msg = "Burning 'em, if you ain't quick and nimble"
+            .as_bytes()
+            .to_vec();
+        let expected = multi_key_xor_encode(&msg, "ICE".as_bytes());
+
+        multi_key_xor_encode_in_place(&mut msg, "ICE".as_bytes());
+        assert_eq!(msg, expected);
+
+        /* XORing with the same key again restores the message */
+        multi_key_xor_encode_in_place(&mut msg, "ICE".as_bytes());
+        assert_eq!(msg, "Burning 'em, if you ain't quick and nimble".as_bytes());
+    }
 }
@@ -0,0 +1,269 @@
+//! This module provides Read and Write adapters which apply a repeating
+//! XOR key to a stream, so large files and sockets can be encoded or
+//! decoded in constant memory
+//!
+//! Both adapters track their position in the stream, so the key carries on
+//! from where the last call left off, and seeking moves through the key too
+
+use crypto_utilities::xor::xor_in_place_with_offset;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Size of the buffer XorWriter encodes into before writing
+const WRITE_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Returns the index of the key byte which applies at the given stream position
+fn key_index(key: &[u8], position: u64) -> usize {
+    (position % key.len() as u64) as usize
+}
+
+/// Checks that a key can be cycled through
+fn check_key(key: &[u8]) -> Result<(), String> {
+    if key.is_empty() {
+        return Err(String::from("Cannot XOR a stream with an empty key"));
+    }
+    Ok(())
+}
+
+/// XORs buf in place with the key, starting at the key byte for position
+fn apply_key(key: &[u8], position: u64, buf: &mut [u8]) {
+    xor_in_place_with_offset(buf, key, key_index(key, position));
+}
+
+/// Wraps a reader, XORing every byte read from it with a repeating key
+#[derive(Debug)]
+pub struct XorReader<R> {
+    inner: R,
+    key: Vec<u8>,
+    position: u64,
+}
+
+impl<R> XorReader<R> {
+    /// Wraps the reader, applying the key from its first byte
+    pub fn new(inner: R, key: &[u8]) -> Result<Self, String> {
+        Self::with_position(inner, key, 0)
+    }
+
+    /// Wraps a reader which is already at the given position in the stream,
+    /// so the first byte read is XORed with the key byte for that position
+    pub fn with_position(inner: R, key: &[u8], position: u64) -> Result<Self, String> {
+        check_key(key)?;
+        Ok(Self {
+            inner,
+            key: key.to_vec(),
+            position,
+        })
+    }
+
+    /// Returns the position in the stream of the next byte to be read
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns a reference to the wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped reader
+    ///
+    /// Reading from or seeking the wrapped reader directly will
+    /// put the adapter out of step with the key
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the adapter, returning the wrapped reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for XorReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = self.inner.read(buf)?;
+        apply_key(&self.key, self.position, &mut buf[..bytes_read]);
+        self.position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+impl<R: Seek> Seek for XorReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+/// Wraps a writer, XORing every byte written to it with a repeating key
+#[derive(Debug)]
+pub struct XorWriter<W> {
+    inner: W,
+    key: Vec<u8>,
+    position: u64,
+}
+
+impl<W> XorWriter<W> {
+    /// Wraps the writer, applying the key from its first byte
+    pub fn new(inner: W, key: &[u8]) -> Result<Self, String> {
+        Self::with_position(inner, key, 0)
+    }
+
+    /// Wraps a writer which is already at the given position in the stream,
+    /// so the first byte written is XORed with the key byte for that position
+    pub fn with_position(inner: W, key: &[u8], position: u64) -> Result<Self, String> {
+        check_key(key)?;
+        Ok(Self {
+            inner,
+            key: key.to_vec(),
+            position,
+        })
+    }
+
+    /// Returns the position in the stream of the next byte to be written
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns a reference to the wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped writer
+    ///
+    /// Writing to or seeking the wrapped writer directly will
+    /// put the adapter out of step with the key
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwraps the adapter, returning the wrapped writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for XorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        /* Encode at most one buffer's worth, so memory use stays constant */
+        let mut encoded = [0; WRITE_BUFFER_SIZE];
+        let len = buf.len().min(WRITE_BUFFER_SIZE);
+        encoded[..len].copy_from_slice(&buf[..len]);
+        apply_key(&self.key, self.position, &mut encoded[..len]);
+
+        /* Only advance through the key by as much as the wrapped writer accepted */
+        let bytes_written = self.inner.write(&encoded[..len])?;
+        self.position += bytes_written as u64;
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for XorWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multi_key_xor_encode;
+    use std::io::Cursor;
+
+    const MSG: &[u8] =
+        b"Burning 'em, if you ain't quick and nimble\nI go crazy when I hear a cymbal";
+    const KEY: &[u8] = b"ICE";
+
+    #[test]
+    fn reader_matches_multi_key_xor_encode() {
+        let mut reader = XorReader::new(MSG, KEY).unwrap();
+
+        /* Read in small, uneven chunks so the key offset has to carry across calls */
+        let mut decoded = Vec::new();
+        let mut chunk = [0; 5];
+        loop {
+            let bytes_read = reader.read(&mut chunk).unwrap();
+            if bytes_read == 0 {
+                break;
+            }
+            decoded.extend_from_slice(&chunk[..bytes_read]);
+        }
+
+        assert_eq!(decoded, multi_key_xor_encode(MSG, KEY));
+        assert_eq!(reader.position(), MSG.len() as u64);
+    }
+
+    #[test]
+    fn writer_matches_multi_key_xor_encode() {
+        let mut writer = XorWriter::new(Vec::new(), KEY).unwrap();
+        for chunk in MSG.chunks(7) {
+            writer.write_all(chunk).unwrap();
+        }
+
+        assert_eq!(writer.into_inner(), multi_key_xor_encode(MSG, KEY));
+    }
+
+    #[test]
+    fn writer_larger_than_buffer() {
+        let msg: Vec<u8> = (0..=255).cycle().take(3 * WRITE_BUFFER_SIZE + 17).collect();
+
+        let mut writer = XorWriter::new(Vec::new(), KEY).unwrap();
+        writer.write_all(&msg).unwrap();
+
+        assert_eq!(writer.into_inner(), multi_key_xor_encode(&msg, KEY));
+    }
+
+    #[test]
+    fn reader_seek() {
+        let encoded = multi_key_xor_encode(MSG, KEY);
+        let mut reader = XorReader::new(Cursor::new(encoded), KEY).unwrap();
+
+        for offset in [10, 0, 31, 1] {
+            assert_eq!(reader.seek(SeekFrom::Start(offset)).unwrap(), offset);
+
+            let mut decoded = [0; 8];
+            reader.read_exact(&mut decoded).unwrap();
+            assert_eq!(&decoded, &MSG[offset as usize..offset as usize + 8]);
+        }
+
+        reader.seek(SeekFrom::End(-5)).unwrap();
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, &MSG[MSG.len() - 5..]);
+    }
+
+    #[test]
+    fn writer_seek_and_overwrite() {
+        let mut writer = XorWriter::new(Cursor::new(Vec::new()), KEY).unwrap();
+        writer.write_all(&vec![b'-'; MSG.len()]).unwrap();
+
+        /* Write the message back to front, so each write starts at a different key offset */
+        for (idx, chunk) in MSG.chunks(4).enumerate().rev() {
+            writer.seek(SeekFrom::Start(idx as u64 * 4)).unwrap();
+            writer.write_all(chunk).unwrap();
+        }
+
+        assert_eq!(
+            writer.into_inner().into_inner(),
+            multi_key_xor_encode(MSG, KEY)
+        );
+    }
+
+    #[test]
+    fn with_position_and_empty_key() {
+        let encoded = multi_key_xor_encode(MSG, KEY);
+        let mut reader = XorReader::with_position(&encoded[4..], KEY, 4).unwrap();
+
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, &MSG[4..]);
+
+        assert!(XorReader::new(MSG, b"").is_err());
+        assert!(XorWriter::new(Vec::<u8>::new(), b"").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! This module brute forces any cipher whose keys are few enough to try every one
+//!
+//! Single byte XOR, the shift ciphers and the affine cipher all have at most a few
+//! hundred keys, so each is cracked the same way: decrypt with every key, have a
+//! scorer judge the results, and rank them. A cipher only has to list its keys
+//! and say how to encrypt and decrypt with one of them
+
+use std::fmt::Debug;
+
+use crypto_utilities::xor::xor_byte_in_place;
+
+use crate::scorer::PlaintextScorer;
+
+/// Number of letters in the alphabet
+const ALPHABET_LEN: u8 = 26;
+
+/// First and last of the printable ASCII characters, excluding space, which ROT47 rotates
+const FIRST_PRINTABLE: u8 = b'!';
+const LAST_PRINTABLE: u8 = b'~';
+
+/// Number of printable ASCII characters rotated by ROT47
+const PRINTABLE_LEN: u8 = LAST_PRINTABLE - FIRST_PRINTABLE + 1;
+
+/// A cipher with few enough keys that every one can be tried
+pub trait SmallKeyspaceCipher: Sync {
+    /// A single key of the cipher
+    type Key: Copy + Debug + PartialEq + Send;
+
+    /// Returns every key of the cipher, in the order they are tried
+    fn keys(&self) -> Vec<Self::Key>;
+
+    /// Encrypts the message in place with the key
+    fn encrypt_in_place(&self, key: Self::Key, msg: &mut [u8]);
+
+    /// Decrypts the message in place with the key
+    fn decrypt_in_place(&self, key: Self::Key, encrypted_msg: &mut [u8]);
+}
+
+/// A candidate solution to a small keyspace cipher, judged by a PlaintextScorer
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyspaceCandidate<K> {
+    /// The key which the message was decrypted with
+    pub key: K,
+    /// The bytes obtained by decrypting with the key
+    pub decoded: Vec<u8>,
+    /// The scorer's judgement of the decoded bytes (lower is better)
+    pub score: f32,
+    /// How much worse the next ranked candidate's score is,
+    /// or None if the scorer rejected every other key
+    pub score_gap: Option<f32>,
+}
+
+/// XOR with a single byte key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SingleByteXor;
+
+impl SmallKeyspaceCipher for SingleByteXor {
+    type Key = u8;
+
+    fn keys(&self) -> Vec<u8> {
+        (0..=u8::MAX).collect()
+    }
+
+    fn encrypt_in_place(&self, key: u8, msg: &mut [u8]) {
+        xor_byte_in_place(msg, key);
+    }
+
+    fn decrypt_in_place(&self, key: u8, encrypted_msg: &mut [u8]) {
+        xor_byte_in_place(encrypted_msg, key);
+    }
+}
+
+/// Shifts each ASCII letter along the alphabet by the key, keeping its case,
+/// as in the Caesar cipher and ROT13, and leaves every other byte alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rot;
+
+impl SmallKeyspaceCipher for Rot {
+    type Key = u8;
+
+    fn keys(&self) -> Vec<u8> {
+        (0..ALPHABET_LEN).collect()
+    }
+
+    fn encrypt_in_place(&self, key: u8, msg: &mut [u8]) {
+        map_letters(msg, |letter| (letter + key % ALPHABET_LEN) % ALPHABET_LEN);
+    }
+
+    fn decrypt_in_place(&self, key: u8, encrypted_msg: &mut [u8]) {
+        self.encrypt_in_place(ALPHABET_LEN - key % ALPHABET_LEN, encrypted_msg);
+    }
+}
+
+/// Shifts each printable ASCII character other than space along the 94 of them
+/// by the key, and leaves every other byte alone. A key of 47 is ROT47
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PrintableRot;
+
+impl PrintableRot {
+    /// The key of ROT47, which undoes itself
+    pub const ROT47: u8 = 47;
+}
+
+impl SmallKeyspaceCipher for PrintableRot {
+    type Key = u8;
+
+    fn keys(&self) -> Vec<u8> {
+        (0..PRINTABLE_LEN).collect()
+    }
+
+    fn encrypt_in_place(&self, key: u8, msg: &mut [u8]) {
+        let key = key % PRINTABLE_LEN;
+        for byte in msg {
+            if (FIRST_PRINTABLE..=LAST_PRINTABLE).contains(byte) {
+                *byte = FIRST_PRINTABLE + (*byte - FIRST_PRINTABLE + key) % PRINTABLE_LEN;
+            }
+        }
+    }
+
+    fn decrypt_in_place(&self, key: u8, encrypted_msg: &mut [u8]) {
+        self.encrypt_in_place(PRINTABLE_LEN - key % PRINTABLE_LEN, encrypted_msg);
+    }
+}
+
+/// A key of the affine cipher, which encrypts the letter x as multiplier * x + shift
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AffineKey {
+    /// The multiplier, which must have no factor in common with 26
+    pub multiplier: u8,
+    /// The shift added after multiplying
+    pub shift: u8,
+}
+
+/// Maps each ASCII letter x to multiplier * x + shift modulo 26, keeping its
+/// case, and leaves every other byte alone. Only the 12 multipliers with an
+/// inverse modulo 26 give a cipher which can be decrypted, so there are 312 keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Affine;
+
+impl SmallKeyspaceCipher for Affine {
+    type Key = AffineKey;
+
+    fn keys(&self) -> Vec<AffineKey> {
+        (1..ALPHABET_LEN)
+            .filter(|&multiplier| get_multiplicative_inverse(multiplier).is_some())
+            .flat_map(|multiplier| {
+                (0..ALPHABET_LEN).map(move |shift| AffineKey { multiplier, shift })
+            })
+            .collect()
+    }
+
+    /// A multiplier with no inverse modulo 26 is still applied, but the message cannot be decrypted
+    fn encrypt_in_place(&self, key: AffineKey, msg: &mut [u8]) {
+        map_letters(msg, |letter| {
+            ((key.multiplier as u16 * letter as u16 + key.shift as u16) % ALPHABET_LEN as u16) as u8
+        });
+    }
+
+    /// A multiplier with no inverse modulo 26 leaves the message unchanged
+    fn decrypt_in_place(&self, key: AffineKey, encrypted_msg: &mut [u8]) {
+        let inverse = match get_multiplicative_inverse(key.multiplier % ALPHABET_LEN) {
+            Some(inverse) => inverse as u16,
+            None => return,
+        };
+
+        map_letters(encrypted_msg, |letter| {
+            let unshifted = (letter as u16 + ALPHABET_LEN as u16
+                - (key.shift % ALPHABET_LEN) as u16)
+                % ALPHABET_LEN as u16;
+            ((inverse * unshifted) % ALPHABET_LEN as u16) as u8
+        });
+    }
+}
+
+/// Takes a message encrypted with the cipher, tries every key, and returns
+/// up to no_of_candidates solutions ranked from most to least likely by the scorer
+///
+/// Keys which the scorer rejects are not ranked, and ties in the
+/// score are broken by the order the cipher lists its keys in
+pub fn crack_small_keyspace<C: SmallKeyspaceCipher + ?Sized>(
+    cipher: &C,
+    encrypted_msg: &[u8],
+    scorer: &dyn PlaintextScorer,
+    no_of_candidates: usize,
+) -> Result<Vec<KeyspaceCandidate<C::Key>>, String> {
+    crack_small_keyspace_column(cipher, encrypted_msg, 0, 1, scorer, no_of_candidates)
+}
+
+/// Ranks the keys for one column of a longer message as crack_small_keyspace does,
+/// where the column is made up of the bytes at offset, offset + stride and so on
+pub fn crack_small_keyspace_column<C: SmallKeyspaceCipher + ?Sized>(
+    cipher: &C,
+    column: &[u8],
+    offset: usize,
+    stride: usize,
+    scorer: &dyn PlaintextScorer,
+    no_of_candidates: usize,
+) -> Result<Vec<KeyspaceCandidate<C::Key>>, String> {
+    let mut candidates = Vec::new();
+
+    for key in cipher.keys() {
+        let mut decoded = column.to_vec();
+        cipher.decrypt_in_place(key, &mut decoded);
+
+        /* A column starting at the first byte with a stride of one is the whole plaintext */
+        let score = if offset == 0 && stride == 1 {
+            scorer.score(&decoded)
+        } else {
+            scorer.score_column(&decoded, offset, stride)
+        };
+
+        /* If the scorer rejects the decoded bytes, skip that key */
+        let score = match score {
+            Some(score) => score,
+            None => continue,
+        };
+
+        candidates.push(KeyspaceCandidate {
+            key,
+            decoded,
+            score,
+            score_gap: None,
+        });
+    }
+
+    if candidates.is_empty() {
+        return Err(String::from(
+            "Did not find any key which resulted in a plausible decoded message",
+        ));
+    }
+
+    /* Rank the candidates by how plausible the scorer found them, keeping key order for ties */
+    candidates.sort_by(|a, b| a.score.total_cmp(&b.score));
+
+    /* Record how far ahead each candidate is of the one ranked below it */
+    for idx in 1..candidates.len() {
+        candidates[idx - 1].score_gap = Some(candidates[idx].score - candidates[idx - 1].score);
+    }
+
+    candidates.truncate(no_of_candidates);
+
+    Ok(candidates)
+}
+
+/// Returns the number which multiplies with the given one to give 1 modulo 26, if there is one
+fn get_multiplicative_inverse(multiplier: u8) -> Option<u8> {
+    (1..ALPHABET_LEN)
+        .find(|&inverse| (multiplier as u16 * inverse as u16) % ALPHABET_LEN as u16 == 1)
+}
+
+/// Replaces each ASCII letter, numbered from 0 for A to 25 for Z,
+/// with the letter f gives for it, keeping its case and leaving
+/// every other byte alone
+pub fn map_letters(msg: &mut [u8], f: impl Fn(u8) -> u8) {
+    for byte in msg {
+        let base = match byte {
+            b'A'..=b'Z' => b'A',
+            b'a'..=b'z' => b'a',
+            _ => continue,
+        };
+        *byte = base + f(*byte - base);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_file_character_percentages, ChiSquaredScorer};
+
+    fn encrypt<C: SmallKeyspaceCipher>(cipher: &C, key: C::Key, msg: &[u8]) -> Vec<u8> {
+        let mut encrypted = msg.to_vec();
+        cipher.encrypt_in_place(key, &mut encrypted);
+        encrypted
+    }
+
+    #[test]
+    fn known_ciphertexts() {
+        assert_eq!(encrypt(&Rot, 13, b"Hello, World!"), b"Uryyb, Jbeyq!");
+        assert_eq!(encrypt(&Rot, 3, b"xyz ABC"), b"abc DEF");
+        assert_eq!(
+            encrypt(&PrintableRot, PrintableRot::ROT47, b"Hello, World!"),
+            b"w6==@[ (@C=5P"
+        );
+
+        /* The affine cipher with multiplier 5 and shift 8 */
+        let key = AffineKey {
+            multiplier: 5,
+            shift: 8,
+        };
+        assert_eq!(encrypt(&Affine, key, b"Affine cipher"), b"Ihhwvc swfrcp");
+
+        assert_eq!(SingleByteXor.keys().len(), 256);
+        assert_eq!(Rot.keys().len(), 26);
+        assert_eq!(PrintableRot.keys().len(), 94);
+        assert_eq!(Affine.keys().len(), 312);
+
+        /* Every key of every cipher is undone by decrypting with it */
+        let msg = b"The quick brown fox jumps over the lazy dog! \xe2\x80\x94 42";
+        fn check_round_trips<C: SmallKeyspaceCipher>(cipher: &C, msg: &[u8]) {
+            for key in cipher.keys() {
+                let mut encrypted = encrypt(cipher, key, msg);
+                cipher.decrypt_in_place(key, &mut encrypted);
+                assert_eq!(encrypted, msg);
+            }
+        }
+        check_round_trips(&SingleByteXor, msg);
+        check_round_trips(&Rot, msg);
+        check_round_trips(&PrintableRot, msg);
+        check_round_trips(&Affine, msg);
+    }
+
+    #[test]
+    fn crack_each_cipher() {
+        let reference_percentages = get_file_character_percentages("reference-text.txt").unwrap();
+        let scorer = ChiSquaredScorer::new(&reference_percentages);
+        let msg = b"Now that the mountain pass was clear of snow, the traders \
+            could bring their goods down to the valley before the autumn fairs.";
+
+        let result = crack_small_keyspace(&Rot, &encrypt(&Rot, 17, msg), &scorer, 3);
+        let candidates = result.unwrap();
+        assert_eq!(candidates[0].key, 17);
+        assert_eq!(candidates[0].decoded, msg);
+        assert!(candidates[0].score_gap.is_some());
+
+        let encrypted = encrypt(&PrintableRot, PrintableRot::ROT47, msg);
+        let result = crack_small_keyspace(&PrintableRot, &encrypted, &scorer, 3);
+        assert_eq!(result.unwrap()[0].key, PrintableRot::ROT47);
+
+        let key = AffineKey {
+            multiplier: 7,
+            shift: 3,
+        };
+        let result = crack_small_keyspace(&Affine, &encrypt(&Affine, key, msg), &scorer, 3);
+        assert_eq!(result.unwrap()[0].key, key);
+
+        let result = crack_small_keyspace(
+            &SingleByteXor,
+            &encrypt(&SingleByteXor, 0x5c, msg),
+            &scorer,
+            3,
+        );
+        assert_eq!(result.unwrap()[0].key, 0x5c);
+
+        /* Bytes outside the printable range stay invalid UTF-8 under every shift */
+        assert!(crack_small_keyspace(&Rot, b"\xff\xfe", &scorer, 3).is_err());
+    }
+}
@@ -1,14 +1,35 @@
 //! This crate provides an API to crack an XOR cipher with a single character key
 //!
 //! Each u8 is tested, and is judged as a valid solution based off character frequency of the English language
+//!
+//! The same brute force also cracks the shift and affine ciphers, see the keyspace module
+//!
+//! Substitution ciphers have too many keys to brute force, so the substitution module searches for the key
 
 use core::str;
-use crypto_utilities::{hex_to_binary_buffer, is_valid_hex};
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, BufRead, BufReader},
-    str::Utf8Error,
+use crypto_utilities::{hex_to_binary_buffer, is_valid_hex, xor::xor_byte_in_place};
+use std::{collections::HashMap, io, str::Utf8Error};
+
+pub mod keyspace;
+pub mod ngram;
+pub mod normalize;
+pub mod profile;
+pub mod scorer;
+pub mod signature;
+pub mod substitution;
+
+pub use keyspace::{
+    crack_small_keyspace, crack_small_keyspace_column, map_letters, Affine, AffineKey,
+    KeyspaceCandidate, PrintableRot, Rot, SingleByteXor, SmallKeyspaceCipher,
+};
+pub use ngram::NgramModel;
+pub use normalize::Normalization;
+pub use profile::FrequencyProfile;
+pub use scorer::{ChiSquaredScorer, PlaintextScorer};
+pub use signature::{classify_file_signature, FileSignatureScorer, FileType};
+pub use substitution::{
+    solve_substitution, solve_substitution_parallel, substitution_decrypt, substitution_encrypt,
+    SubstitutionKey, SubstitutionOptions, SubstitutionSolution,
 };
 
 /// Takes a file and calculates the reference percentages before
@@ -48,6 +69,24 @@ pub fn single_xor_hex_cipher_crack(
     single_xor_cipher_crack(&encoded_bytes, reference_percentages)
 }
 
+/// A candidate solution to a single byte XOR cipher
+#[derive(Debug, Clone, PartialEq)]
+pub struct SingleXorCandidate {
+    /// The key which was XORed with the encoded message
+    pub key: u8,
+    /// The message obtained by decoding with the key
+    pub message: String,
+    /// The chi-squared metric between the message's character
+    /// frequencies and the reference frequencies (lower is better)
+    pub chi_squared: f32,
+    /// How much worse the next ranked candidate's chi-squared metric is,
+    /// or None if no other key produced a valid UTF-8 message
+    pub score_gap: Option<f32>,
+}
+
+/// A candidate solution to a single byte XOR cipher, judged by a PlaintextScorer
+pub type ScoredXorCandidate = KeyspaceCandidate<u8>;
+
 /// Takes binary buffer which has been encoded by a single byte XOR,
 /// and uses brute force and character frequency analysis to
 /// get the most likely solution
@@ -57,41 +96,162 @@ pub fn single_xor_cipher_crack(
     encoded_bytes: &[u8],
     reference_percentages: &HashMap<char, f32>,
 ) -> Result<(u8, String, f32), String> {
-    /* Keep track of key and message which have most similar character frequencies to the sample text */
-    let mut smallest_chi = None;
-    let mut decoded_message = None;
-    let mut best_key = None;
-
-    /* Try each single byte key */
-    for key in 0..255 {
-        /* If decoding each byte with XOR does not result in a valid UTF-8 string, skip that iteration */
-        let decode_attempt = match apply_xor_cipher(key, encoded_bytes) {
-            Ok(decode_attempt) => decode_attempt,
-            Err(_) => continue,
+    let best =
+        single_xor_cipher_crack_ranked(encoded_bytes, reference_percentages, 1)?.swap_remove(0);
+
+    Ok((best.key, best.message, best.chi_squared))
+}
+
+/// Takes binary buffer which has been encoded by a single byte XOR,
+/// tries every possible key, and returns up to no_of_candidates
+/// solutions ranked from most to least likely
+///
+/// Keys whose decoded message is not valid UTF-8 are not ranked, and
+/// ties in the chi-squared metric are broken by the smaller key
+pub fn single_xor_cipher_crack_ranked(
+    encoded_bytes: &[u8],
+    reference_percentages: &HashMap<char, f32>,
+    no_of_candidates: usize,
+) -> Result<Vec<SingleXorCandidate>, String> {
+    single_xor_cipher_crack_ranked_normalized(
+        encoded_bytes,
+        reference_percentages,
+        &Normalization::default(),
+        no_of_candidates,
+    )
+}
+
+/// Takes binary buffer which has been encoded by a single byte XOR,
+/// and uses brute force and character frequency analysis to
+/// get the most likely solution
+///
+/// Each decoded message is normalized before its character frequencies are
+/// compared, so the reference percentages should be built with the same normalization
+///
+/// On success, it will return the key and the decoded message string as it
+/// was before normalization
+pub fn single_xor_cipher_crack_normalized(
+    encoded_bytes: &[u8],
+    reference_percentages: &HashMap<char, f32>,
+    normalization: &Normalization,
+) -> Result<(u8, String, f32), String> {
+    let best = single_xor_cipher_crack_ranked_normalized(
+        encoded_bytes,
+        reference_percentages,
+        normalization,
+        1,
+    )?
+    .swap_remove(0);
+
+    Ok((best.key, best.message, best.chi_squared))
+}
+
+/// Ranks the solutions to a single byte XOR cipher as single_xor_cipher_crack_ranked
+/// does, normalizing each decoded message before its character frequencies are compared
+pub fn single_xor_cipher_crack_ranked_normalized(
+    encoded_bytes: &[u8],
+    reference_percentages: &HashMap<char, f32>,
+    normalization: &Normalization,
+    no_of_candidates: usize,
+) -> Result<Vec<SingleXorCandidate>, String> {
+    let scorer = ChiSquaredScorer::with_normalization(reference_percentages, *normalization);
+
+    /*
+     * The chi-squared scorer rejects every key which does not decode to valid UTF-8,
+     * or whose decoding normalization leaves empty
+     */
+    let candidates =
+        match single_xor_column_crack_ranked(encoded_bytes, 0, 1, &scorer, no_of_candidates) {
+            Ok(candidates) => candidates,
+            Err(_) => {
+                return Err(String::from(
+                    "Did not find any key which resulted in a valid decoded UTF-8 string",
+                ))
+            }
         };
 
-        let decoded_percentages = get_character_percentages(&decode_attempt);
+    Ok(candidates
+        .into_iter()
+        .map(|candidate| SingleXorCandidate {
+            key: candidate.key,
+            message: String::from_utf8(candidate.decoded).unwrap(),
+            chi_squared: candidate.score,
+            score_gap: candidate.score_gap,
+        })
+        .collect())
+}
+
+/// Takes binary buffer which has been encoded by a single byte XOR,
+/// and uses brute force and the given scorer to get the most likely solution
+///
+/// On success, it will return the key, the decoded bytes and their score
+pub fn single_xor_cipher_crack_with_scorer(
+    encoded_bytes: &[u8],
+    scorer: &dyn PlaintextScorer,
+) -> Result<(u8, Vec<u8>, f32), String> {
+    let best = single_xor_cipher_crack_ranked_with_scorer(encoded_bytes, scorer, 1)?.swap_remove(0);
+
+    Ok((best.key, best.decoded, best.score))
+}
 
-        let new_chi = get_chi_squared(reference_percentages, decoded_percentages);
+/// Takes binary buffer which has been encoded by a single byte XOR, tries
+/// every possible key, and returns up to no_of_candidates solutions ranked
+/// from most to least likely by the given scorer
+pub fn single_xor_cipher_crack_ranked_with_scorer(
+    encoded_bytes: &[u8],
+    scorer: &dyn PlaintextScorer,
+    no_of_candidates: usize,
+) -> Result<Vec<ScoredXorCandidate>, String> {
+    single_xor_column_crack_ranked(encoded_bytes, 0, 1, scorer, no_of_candidates)
+}
 
-        /*
-         * If this key results in a decoded message with more similar character frequencies
-         * to the reference text, update the provisional return values
-         */
-        if smallest_chi.is_none() || new_chi < smallest_chi.unwrap() {
-            smallest_chi = Some(new_chi);
-            decoded_message = Some(decode_attempt);
-            best_key = Some(key);
+/// Takes one column of a message encoded with a repeating key, made up of the
+/// bytes at offset, offset + stride and so on, tries every possible key byte,
+/// and returns up to no_of_candidates solutions ranked by the given scorer
+///
+/// Keys which the scorer rejects are not ranked, and ties in
+/// the score are broken by the smaller key
+pub fn single_xor_column_crack_ranked(
+    column: &[u8],
+    offset: usize,
+    stride: usize,
+    scorer: &dyn PlaintextScorer,
+    no_of_candidates: usize,
+) -> Result<Vec<ScoredXorCandidate>, String> {
+    crack_small_keyspace_column(
+        &SingleByteXor,
+        column,
+        offset,
+        stride,
+        scorer,
+        no_of_candidates,
+    )
+}
+
+/// Takes binary buffer which has been encoded by a single byte XOR,
+/// and cracks it under each of the named language profiles given
+///
+/// On success, it will return the key, decoded message string and chi-squared
+/// metric of the best solution, along with the name of the language it was found in
+pub fn single_xor_cipher_crack_languages<'a>(
+    encoded_bytes: &[u8],
+    language_percentages: &[(&'a str, &HashMap<char, f32>)],
+) -> Result<(u8, String, f32, &'a str), String> {
+    let mut best = None;
+
+    for &(language, reference_percentages) in language_percentages {
+        /* If no key decodes to valid UTF-8 under this language, it will not under any other */
+        let (key, message, chi_squared) =
+            single_xor_cipher_crack(encoded_bytes, reference_percentages)?;
+
+        /* If this language's solution is more plausible, record it */
+        match best {
+            Some((_, _, smallest_chi, _)) if smallest_chi <= chi_squared => (),
+            _ => best = Some((key, message, chi_squared, language)),
         }
     }
 
-    if let Some(smallest_chi) = smallest_chi {
-        Ok((best_key.unwrap(), decoded_message.unwrap(), smallest_chi))
-    } else {
-        Err(String::from(
-            "Did not find any key which resulted in a valid decoded UTF-8 string",
-        ))
-    }
+    best.ok_or_else(|| String::from("No language profiles were given"))
 }
 
 /// Returns a String resulting from XORing the key with every byte in the encoded message
@@ -99,15 +259,13 @@ pub fn single_xor_cipher_crack(
 /// If the resulting bytes are not a valid UTF-8 sequence, an error is returned
 pub fn apply_xor_cipher(key: u8, encoded_msg: &[u8]) -> Result<String, Utf8Error> {
     /* Get the decoded bytes by XORing every byte in the encoded message with the key */
-    let decoded_bytes: Vec<u8> = encoded_msg
-        .iter()
-        .map(|encoded_byte| encoded_byte ^ key)
-        .collect();
+    let mut decoded_bytes = encoded_msg.to_vec();
+    xor_byte_in_place(&mut decoded_bytes, key);
 
     /* Try and decode the bytes to a String, and return the String if successful */
-    match str::from_utf8(&decoded_bytes) {
-        Ok(decoded_str) => Ok(String::from(decoded_str)),
-        Err(utf8_error) => Err(utf8_error),
+    match String::from_utf8(decoded_bytes) {
+        Ok(decoded_str) => Ok(decoded_str),
+        Err(from_utf8_error) => Err(from_utf8_error.utf8_error()),
     }
 }
 
@@ -123,6 +281,19 @@ pub fn get_character_percentages(text: &str) -> HashMap<char, f32> {
         .collect()
 }
 
+/// Given a &str, returns the frequency at which each character appears
+/// in the text as a percentage, after the text has been normalized
+pub fn get_normalized_character_percentages(
+    text: &str,
+    normalization: &Normalization,
+) -> HashMap<char, f32> {
+    if normalization.is_identity() {
+        return get_character_percentages(text);
+    }
+
+    get_character_percentages(&normalization.normalize(text))
+}
+
 /// Given a &str, returns the number of times
 /// each character appears in the text
 pub fn get_character_frequencies(text: &str) -> HashMap<char, u32> {
@@ -137,54 +308,38 @@ pub fn get_character_frequencies(text: &str) -> HashMap<char, u32> {
 
 /// Get the text from the given file and return a hashmap containing the character frequency percentages
 pub fn get_file_character_percentages(filename: &str) -> io::Result<HashMap<char, f32>> {
-    let file = File::open(filename)?;
-
-    /* Allocate a large buffer to memory map the file and speed up reading */
-    let mut reader = BufReader::with_capacity(300 * 1024, file);
-
-    /* Keeps a running count of how many times each character has appeared in the file */
-    let mut counts = HashMap::new();
-    let mut total_chars = 0;
-
-    let mut line = String::new();
+    let profile = FrequencyProfile::from_files(&[filename])?;
 
-    while reader.read_line(&mut line)? > 0 {
-        /* Get the character frequencies in each line, and update counts */
-        for (c, frequency) in get_character_frequencies(&line) {
-            counts
-                .entry(c)
-                .and_modify(|count| *count += frequency)
-                .or_insert(frequency);
-            total_chars += frequency;
-        }
-
-        /* Remember the newline character too */
-        counts
-            .entry('\n')
-            .and_modify(|count| *count += 1)
-            .or_insert(1);
-        total_chars += 1;
-
-        line.clear();
-    }
-
-    let frequencies = counts
-        .into_iter()
-        .map(|(c, count)| (c, count as f32 / (total_chars / 100) as f32))
-        .collect();
+    Ok(profile.percentages())
+}
 
-    Ok(frequencies)
+/// Get the text from the given file, normalize it, and return a hashmap
+/// containing the character frequency percentages
+///
+/// Unlike get_file_character_percentages, the file's newlines are counted as
+/// they are written, so the percentages match those of candidate decodings
+pub fn get_file_normalized_character_percentages(
+    filename: &str,
+    normalization: &Normalization,
+) -> io::Result<HashMap<char, f32>> {
+    let mut profile = FrequencyProfile::new();
+    profile.add_normalized_file(filename, normalization)?;
+
+    Ok(profile.percentages())
 }
 
 /// Calculate the chi-squared metric on two character
 /// frequency sets to determine how 'similar' they are
+///
+/// The terms are summed in character order, so that the result does not
+/// depend on the iteration order of the hashmaps
 pub fn get_chi_squared(reference: &HashMap<char, f32>, mut msg: HashMap<char, f32>) -> f32 {
-    let mut chi_squared = 0.0;
+    let mut terms = Vec::with_capacity(reference.len() + msg.len());
 
     /* First iterate over the reference frequencies, and get the chi squared values for all of those characters */
     for (char, frequency) in reference {
         let difference = msg.get(char).unwrap_or(&0.0) - frequency;
-        chi_squared += (difference * difference) / frequency;
+        terms.push((*char, (difference * difference) / frequency));
 
         /* Once a character has been processed, remove it from the message map */
         msg.remove(char);
@@ -193,10 +348,11 @@ pub fn get_chi_squared(reference: &HashMap<char, f32>, mut msg: HashMap<char, f3
     /* Now process the rest of the characters */
     for (char, frequency) in msg {
         let difference = reference.get(&char).unwrap_or(&0.0) - frequency;
-        chi_squared += (difference * difference) / frequency;
+        terms.push((char, (difference * difference) / frequency));
     }
 
-    chi_squared
+    terms.sort_unstable_by_key(|&(char, _)| char);
+    terms.into_iter().map(|(_, term)| term).sum()
 }
 
 #[cfg(test)]
@@ -278,4 +434,165 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn crack_cipher_ranked() {
+        let encoded = hex_to_binary_buffer(
+            "1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736",
+        )
+        .unwrap();
+        let reference_percentages = get_file_character_percentages("sample-text.txt").unwrap();
+
+        let result = single_xor_cipher_crack_ranked(&encoded, &reference_percentages, 5);
+        assert!(result.is_ok());
+
+        let candidates = result.unwrap();
+        assert_eq!(candidates.len(), 5);
+        assert_eq!(candidates[0].key, 88);
+        assert_eq!(candidates[0].message, "Cooking MC's like a pound of bacon");
+
+        /* Candidates are ranked in ascending chi-squared order, with non-negative gaps between them */
+        for pair in candidates.windows(2) {
+            assert!(pair[0].chi_squared <= pair[1].chi_squared);
+            assert_eq!(
+                pair[0].score_gap,
+                Some(pair[1].chi_squared - pair[0].chi_squared)
+            );
+        }
+    }
+
+    #[test]
+    fn file_character_percentages() {
+        let percentages = get_file_character_percentages("small-text.txt").unwrap();
+
+        /* The newline is counted twice, as read and as remembered, and so few characters still give exact percentages */
+        let expected = HashMap::from([('a', 40.0), ('b', 30.0), ('c', 10.0), ('\n', 20.0)]);
+        assert_eq!(percentages, expected);
+        assert!(get_file_character_percentages("missing.txt").is_err());
+    }
+
+    #[test]
+    fn crack_cipher_key_255() {
+        let reference_percentages = get_character_percentages("hello world");
+        let encoded: Vec<u8> = "hello world".bytes().map(|byte| byte ^ 0xff).collect();
+
+        let result = single_xor_cipher_crack(&encoded, &reference_percentages);
+        assert!(result.is_ok());
+
+        let (key, message, _) = result.unwrap();
+        assert_eq!(key, 0xff);
+        assert_eq!(message, "hello world");
+    }
+
+    #[test]
+    fn crack_cipher_languages() {
+        let english = get_file_character_percentages("reference-text.txt").unwrap();
+        let german = get_file_character_percentages("sample-text-de.txt").unwrap();
+        let languages = [("english", &english), ("german", &german)];
+
+        let test_data = [
+            (
+                "Neither the name of the copyright holder nor the names of its contributors",
+                "english",
+            ),
+            (
+                "Im Winter saßen die Familien am Abend um den warmen Ofen und erzählten sich Geschichten",
+                "german",
+            ),
+        ];
+
+        for (message, language) in test_data {
+            let encoded: Vec<u8> = message.bytes().map(|byte| byte ^ 0x2a).collect();
+
+            let result = single_xor_cipher_crack_languages(&encoded, &languages);
+            assert!(result.is_ok());
+
+            let (key, decoded, _, decoded_language) = result.unwrap();
+            assert_eq!(key, 0x2a);
+            assert_eq!(decoded, message);
+            assert_eq!(decoded_language, language);
+        }
+    }
+
+    #[test]
+    fn crack_cipher_no_languages() {
+        let result = single_xor_cipher_crack_languages("abc".as_bytes(), &[]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn crack_cipher_normalized() {
+        let normalization = Normalization::letters_only();
+        let reference_percentages =
+            get_file_normalized_character_percentages("sample-text.txt", &normalization).unwrap();
+
+        let encoded = hex_to_binary_buffer(
+            "1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736",
+        )
+        .unwrap();
+
+        let result =
+            single_xor_cipher_crack_normalized(&encoded, &reference_percentages, &normalization);
+        assert!(result.is_ok());
+
+        let (key, message, _) = result.unwrap();
+        assert_eq!(key, 88);
+        assert_eq!(message, "Cooking MC's like a pound of bacon");
+    }
+
+    #[test]
+    fn crack_cipher_normalized_rejects_letterless() {
+        let normalization = Normalization::letters_only();
+        let reference_percentages =
+            get_file_normalized_character_percentages("sample-text.txt", &normalization).unwrap();
+
+        /* XORing lowercase letters and spaces with 0x40 leaves only digits and symbols */
+        let encoded: Vec<u8> = b"jazz quiz".iter().map(|byte| byte ^ 0x2a).collect();
+        let letterless_key = 0x2a ^ 0x40;
+
+        let result = single_xor_cipher_crack_ranked_normalized(
+            &encoded,
+            &reference_percentages,
+            &normalization,
+            256,
+        );
+        assert!(result.is_ok());
+
+        let candidates = result.unwrap();
+        assert!(candidates
+            .iter()
+            .all(|candidate| candidate.key != letterless_key));
+        assert!(candidates
+            .iter()
+            .all(|candidate| candidate.message.chars().any(char::is_alphabetic)));
+
+        let scorer = ChiSquaredScorer::with_normalization(&reference_percentages, normalization);
+        assert_eq!(scorer.score(b"*!::`15):"), None);
+        assert!(scorer.score(b"jazz quiz").is_some());
+    }
+
+    #[test]
+    fn get_normalized_character_percentages_simple() {
+        let result =
+            get_normalized_character_percentages("A a, b B!", &Normalization::letters_only());
+        let mut expected = HashMap::new();
+        expected.insert('a', 50.0);
+        expected.insert('b', 50.0);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn crack_cipher_with_file_signature_scorer() {
+        let gzip = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03\xcb\x48\xcd\xc9\xc9\x07\x00";
+        let encoded: Vec<u8> = gzip.iter().map(|byte| byte ^ 0x5c).collect();
+
+        let result = single_xor_cipher_crack_with_scorer(&encoded, &FileSignatureScorer::new());
+        assert!(result.is_ok());
+
+        let (key, decoded, score) = result.unwrap();
+        assert_eq!(key, 0x5c);
+        assert_eq!(decoded, gzip);
+        assert_eq!(score, 0.0);
+    }
 }
@@ -0,0 +1,209 @@
+//! This module provides a character n-gram language model, which scores a
+//! plaintext one byte at a time by how likely each byte is to follow the ones before it
+//!
+//! Unlike the frequency profiles, the model knows about the order of characters,
+//! so it can judge very short fragments and can be used to build plaintexts up
+//! incrementally. Probabilities of each order are smoothed towards the order
+//! below, so bytes never seen in the reference text still have a small probability
+
+use std::{collections::HashMap, fs, io};
+
+use crate::PlaintextScorer;
+
+/// Longest n-gram the model can count, since n-grams are packed into a u64
+pub const MAX_ORDER: usize = 8;
+
+/// Weight given to the order below when smoothing the probabilities of each order
+const SMOOTHING_WEIGHT: f64 = 1.0;
+
+/// Number of distinct byte values
+const BYTE_VALUES: f64 = 256.0;
+
+/// Counts of byte n-grams in a body of reference text
+#[derive(Debug, Clone, PartialEq)]
+pub struct NgramModel {
+    order: usize,
+    /* ngram_counts[len] holds the counts of the n-grams of len + 1 bytes */
+    ngram_counts: Vec<HashMap<u64, u64>>,
+    /* context_counts[len] holds how often each context of len bytes is followed by a byte */
+    context_counts: Vec<HashMap<u64, u64>>,
+}
+
+impl NgramModel {
+    /// Returns an empty model which conditions each byte on the order - 1 bytes before it
+    pub fn new(order: usize) -> Result<Self, String> {
+        if order == 0 || order > MAX_ORDER {
+            return Err(format!(
+                "The order of an n-gram model must be between 1 and {}",
+                MAX_ORDER
+            ));
+        }
+
+        Ok(Self {
+            order,
+            ngram_counts: vec![HashMap::new(); order],
+            context_counts: vec![HashMap::new(); order],
+        })
+    }
+
+    /// Returns a model of the given order built from the reference text
+    pub fn from_corpus(corpus: &[u8], order: usize) -> Result<Self, String> {
+        let mut model = Self::new(order)?;
+        model.add_corpus(corpus);
+        Ok(model)
+    }
+
+    /// Returns a model of the given order built from each of the reference files
+    pub fn from_files(filenames: &[&str], order: usize) -> Result<Self, String> {
+        let mut model = Self::new(order)?;
+        for filename in filenames {
+            model.add_file(filename).map_err(|e| e.to_string())?;
+        }
+        Ok(model)
+    }
+
+    /// Adds the n-grams in the reference text to the model
+    pub fn add_corpus(&mut self, corpus: &[u8]) {
+        for idx in 0..corpus.len() {
+            for context_len in 0..self.order.min(idx + 1) {
+                let ngram = &corpus[idx - context_len..=idx];
+
+                *self.ngram_counts[context_len]
+                    .entry(pack(ngram))
+                    .or_insert(0) += 1;
+                *self.context_counts[context_len]
+                    .entry(pack(&ngram[..context_len]))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Adds the n-grams in the reference file to the model
+    pub fn add_file(&mut self, filename: &str) -> io::Result<()> {
+        let corpus = fs::read(filename)?;
+        self.add_corpus(&corpus);
+        Ok(())
+    }
+
+    /// Returns the number of bytes in each n-gram, including the byte being predicted
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Returns the natural log of the probability that next follows the context
+    ///
+    /// Only the last order - 1 bytes of the context are used, and a shorter context
+    /// is used as is, as happens at the start of a plaintext
+    pub fn log_probability(&self, context: &[u8], next: u8) -> f64 {
+        let context = &context[context.len().saturating_sub(self.order - 1)..];
+
+        /* Start from the uniform distribution, and refine it with each longer context */
+        let mut probability = 1.0 / BYTE_VALUES;
+        let mut ngram = Vec::with_capacity(context.len() + 1);
+
+        for context_len in 0..=context.len() {
+            let context = &context[context.len() - context_len..];
+
+            let context_count = match self.context_counts[context_len].get(&pack(context)) {
+                Some(&count) => count as f64,
+                /* A longer context containing this one cannot have been seen either */
+                None => break,
+            };
+
+            ngram.clear();
+            ngram.extend_from_slice(context);
+            ngram.push(next);
+            let ngram_count = self.ngram_counts[context_len]
+                .get(&pack(&ngram))
+                .map_or(0.0, |&count| count as f64);
+
+            probability =
+                (ngram_count + SMOOTHING_WEIGHT * probability) / (context_count + SMOOTHING_WEIGHT);
+        }
+
+        probability.ln()
+    }
+
+    /// Returns the natural log of the probability of the whole text
+    pub fn log_likelihood(&self, text: &[u8]) -> f64 {
+        (0..text.len())
+            .map(|idx| self.log_probability(&text[..idx], text[idx]))
+            .sum()
+    }
+}
+
+/// Scores plaintexts by their average negative log probability per byte,
+/// so that plaintexts of different lengths can be compared
+///
+/// Any bytes can be scored, so the model suits plaintexts in any encoding,
+/// or binary formats, as long as it is built from reference files of the same kind
+impl PlaintextScorer for NgramModel {
+    fn score(&self, plaintext: &[u8]) -> Option<f32> {
+        if plaintext.is_empty() {
+            return None;
+        }
+
+        Some((-self.log_likelihood(plaintext) / plaintext.len() as f64) as f32)
+    }
+
+    /* The bytes of a column are not next to each other in the plaintext, so they have no context */
+    fn score_column(&self, column: &[u8], _offset: usize, _stride: usize) -> Option<f32> {
+        if column.is_empty() {
+            return None;
+        }
+
+        let log_likelihood: f64 = column
+            .iter()
+            .map(|&byte| self.log_probability(&[], byte))
+            .sum();
+
+        Some((-log_likelihood / column.len() as f64) as f32)
+    }
+}
+
+/// Packs up to MAX_ORDER bytes into a u64, to be used as a map key
+fn pack(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |packed, &byte| (packed << 8) | byte as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probabilities_sum_to_one() {
+        let model = NgramModel::from_corpus(b"the cat sat on the mat with the hat", 3).unwrap();
+
+        /* Cover a seen context, an unseen context and a context shorter than the order */
+        for context in [&b"th"[..], b"zq", b"t", b""] {
+            let total: f64 = (0..=255)
+                .map(|next| model.log_probability(context, next).exp())
+                .sum();
+            assert!((total - 1.0).abs() < 1e-9);
+        }
+
+        assert!(model.log_probability(b"th", b'e') > model.log_probability(b"th", b'q'));
+        assert!(NgramModel::new(0).is_err());
+        assert!(NgramModel::new(MAX_ORDER + 1).is_err());
+    }
+
+    #[test]
+    fn ngram_scorer_prefers_english() {
+        let result = NgramModel::from_files(&["sample-text.txt"], 4);
+        assert!(result.is_ok());
+
+        let model = result.unwrap();
+        let english = model.score(b"the software is provided as is");
+        let shuffled = model.score(b"ai ts tdrve eost f iprohsw wae");
+        assert!(english.unwrap() < shuffled.unwrap());
+        assert_eq!(model.score(b""), None);
+
+        /* Columns are judged on their bytes alone, so their order does not matter */
+        let english = model.score_column(b"the software is provided as is", 1, 3);
+        let reversed = model.score_column(b"si sa dedivorp si erawtfos eht", 1, 3);
+        assert!((english.unwrap() - reversed.unwrap()).abs() < 1e-5);
+        assert_eq!(model.score_column(b"", 1, 3), None);
+    }
+}
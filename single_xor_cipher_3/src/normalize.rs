@@ -0,0 +1,120 @@
+//! This module provides configurable text normalization, so that reference
+//! text and candidate decodings can be put through the same pipeline before
+//! their character frequencies are compared
+
+/// The normalization steps to apply to text before counting its characters
+///
+/// The default performs no normalization, so characters are counted exactly as written
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Normalization {
+    /// Convert every character to lowercase
+    pub fold_case: bool,
+    /// Replace each run of whitespace with a single space
+    pub collapse_whitespace: bool,
+    /// Remove punctuation and symbols
+    pub strip_punctuation: bool,
+    /// Remove every character which is not alphabetic, including whitespace
+    pub alphabetic_only: bool,
+}
+
+impl Normalization {
+    /// Returns normalization which counts only letters, ignoring their case
+    pub fn letters_only() -> Self {
+        Self {
+            fold_case: true,
+            alphabetic_only: true,
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if this normalization leaves text unchanged
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns the text after applying each of the enabled normalization steps
+    pub fn normalize(&self, text: &str) -> String {
+        let mut normalized = String::with_capacity(text.len());
+
+        /* Tracks whether the last character pushed was collapsed whitespace */
+        let mut in_whitespace = false;
+
+        for c in text.chars() {
+            if (self.alphabetic_only && !c.is_alphabetic())
+                || (self.strip_punctuation && is_punctuation(c))
+            {
+                continue;
+            }
+
+            if self.collapse_whitespace && c.is_whitespace() {
+                if !in_whitespace {
+                    normalized.push(' ');
+                    in_whitespace = true;
+                }
+                continue;
+            }
+            in_whitespace = false;
+
+            if self.fold_case {
+                normalized.extend(c.to_lowercase());
+            } else {
+                normalized.push(c);
+            }
+        }
+
+        normalized
+    }
+}
+
+/// Determines if a character is punctuation or a symbol, which is
+/// anything visible that is neither a letter nor a digit
+fn is_punctuation(c: char) -> bool {
+    !c.is_alphanumeric() && !c.is_whitespace() && !c.is_control()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_normalization() {
+        let text = "Hello,  World!\n“Quoted”";
+        assert!(Normalization::default().is_identity());
+        assert_eq!(Normalization::default().normalize(text), text);
+    }
+
+    #[test]
+    fn each_normalization_step() {
+        let text = "Hello,  World!\n“Quoted” 42";
+
+        let test_data = [
+            (
+                Normalization {
+                    fold_case: true,
+                    ..Normalization::default()
+                },
+                "hello,  world!\n“quoted” 42",
+            ),
+            (
+                Normalization {
+                    collapse_whitespace: true,
+                    ..Normalization::default()
+                },
+                "Hello, World! “Quoted” 42",
+            ),
+            (
+                Normalization {
+                    strip_punctuation: true,
+                    collapse_whitespace: true,
+                    ..Normalization::default()
+                },
+                "Hello World Quoted 42",
+            ),
+            (Normalization::letters_only(), "helloworldquoted"),
+        ];
+
+        for (normalization, expected) in test_data {
+            assert_eq!(normalization.normalize(text), expected);
+        }
+    }
+}
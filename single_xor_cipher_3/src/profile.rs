@@ -0,0 +1,292 @@
+//! This module provides character frequency profiles which can be built
+//! once from one or many reference files, saved to disk, and loaded back
+//! without having to re-read the reference text
+//!
+//! Profiles store raw character counts rather than percentages, so that
+//! they can be merged and updated incrementally without losing precision
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+};
+
+use crate::{get_character_frequencies, Normalization};
+
+/// Bytes at the start of every serialised profile
+const PROFILE_MAGIC: &[u8; 4] = b"CFP\0";
+
+/// Version of the serialised profile format
+const PROFILE_VERSION: u8 = 1;
+
+/// Size of the header: magic, version, total characters and number of entries
+const HEADER_LEN: usize = PROFILE_MAGIC.len() + 1 + 8 + 4;
+
+/// Size of each entry: the character's code point and its count
+const ENTRY_LEN: usize = 4 + 8;
+
+/// Number of times each character appears in a body of reference text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrequencyProfile {
+    counts: HashMap<char, u64>,
+    total_chars: u64,
+}
+
+impl FrequencyProfile {
+    /// Returns an empty profile
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a profile built from the given text
+    pub fn from_text(text: &str) -> Self {
+        let mut profile = Self::new();
+        profile.add_text(text);
+        profile
+    }
+
+    /// Returns a profile built from each of the given reference files
+    pub fn from_files(filenames: &[&str]) -> io::Result<Self> {
+        let mut profile = Self::new();
+        for filename in filenames {
+            profile.add_file(filename)?;
+        }
+        Ok(profile)
+    }
+
+    /// Adds the characters in the given text to the profile
+    pub fn add_text(&mut self, text: &str) {
+        for (c, frequency) in get_character_frequencies(text) {
+            self.add_count(c, frequency as u64);
+        }
+    }
+
+    /// Adds the characters in the given reference file to the profile
+    ///
+    /// A newline is counted for every line read, matching get_file_character_percentages
+    pub fn add_file(&mut self, filename: &str) -> io::Result<()> {
+        let file = File::open(filename)?;
+
+        /* Allocate a large buffer to memory map the file and speed up reading */
+        let mut reader = BufReader::with_capacity(300 * 1024, file);
+        let mut line = String::new();
+
+        while reader.read_line(&mut line)? > 0 {
+            self.add_text(&line);
+
+            /* Remember the newline character too */
+            self.add_count('\n', 1);
+
+            line.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Adds the characters in the given reference file to the profile,
+    /// after normalizing the whole of its text
+    ///
+    /// No synthetic newlines are counted, so whitespace is treated exactly
+    /// as it would be in a normalized candidate decoding
+    pub fn add_normalized_file(
+        &mut self,
+        filename: &str,
+        normalization: &Normalization,
+    ) -> io::Result<()> {
+        let text = fs::read_to_string(filename)?;
+        self.add_text(&normalization.normalize(&text));
+
+        Ok(())
+    }
+
+    /// Adds the counts from another profile to this one
+    pub fn merge(&mut self, other: &FrequencyProfile) {
+        for (&c, &count) in &other.counts {
+            self.add_count(c, count);
+        }
+    }
+
+    /// Returns the number of times each character has been seen
+    pub fn counts(&self) -> &HashMap<char, u64> {
+        &self.counts
+    }
+
+    /// Returns the total number of characters that have been seen
+    pub fn total_chars(&self) -> u64 {
+        self.total_chars
+    }
+
+    /// Returns the frequency at which each character appears in
+    /// the profile as a percentage, ready to be passed to the crackers
+    pub fn percentages(&self) -> HashMap<char, f32> {
+        self.counts
+            .iter()
+            .map(|(&c, &count)| (c, (count as f64 * 100.0 / self.total_chars as f64) as f32))
+            .collect()
+    }
+
+    /// Serialises the profile into its compact binary format
+    ///
+    /// Entries are written in code point order so that equal
+    /// profiles always produce identical bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<(&char, &u64)> = self.counts.iter().collect();
+        entries.sort();
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + entries.len() * ENTRY_LEN);
+        bytes.extend_from_slice(PROFILE_MAGIC);
+        bytes.push(PROFILE_VERSION);
+        bytes.extend_from_slice(&self.total_chars.to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+
+        for (&c, &count) in entries {
+            bytes.extend_from_slice(&(c as u32).to_le_bytes());
+            bytes.extend_from_slice(&count.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Deserialises a profile from its compact binary format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < HEADER_LEN || &bytes[..PROFILE_MAGIC.len()] != PROFILE_MAGIC {
+            return Err(String::from("Data is not a character frequency profile"));
+        }
+
+        let version = bytes[PROFILE_MAGIC.len()];
+        if version != PROFILE_VERSION {
+            return Err(format!(
+                "Unsupported character frequency profile version: {}",
+                version
+            ));
+        }
+
+        /* The header lengths are fixed so these conversions cannot fail */
+        let total_start = PROFILE_MAGIC.len() + 1;
+        let total_chars =
+            u64::from_le_bytes(bytes[total_start..total_start + 8].try_into().unwrap());
+        let no_of_entries =
+            u32::from_le_bytes(bytes[total_start + 8..HEADER_LEN].try_into().unwrap()) as usize;
+
+        let entries = &bytes[HEADER_LEN..];
+        if entries.len() != no_of_entries * ENTRY_LEN {
+            return Err(format!(
+                "Expected {} bytes of profile entries, got {}",
+                no_of_entries * ENTRY_LEN,
+                entries.len()
+            ));
+        }
+
+        let mut profile = Self::new();
+        for entry in entries.chunks_exact(ENTRY_LEN) {
+            let code_point = u32::from_le_bytes(entry[..4].try_into().unwrap());
+            let c = match char::from_u32(code_point) {
+                Some(c) => c,
+                None => return Err(format!("Invalid character in profile: {:#x}", code_point)),
+            };
+            let count = u64::from_le_bytes(entry[4..].try_into().unwrap());
+
+            if profile.counts.insert(c, count).is_some() {
+                return Err(format!("Duplicate character in profile: {:?}", c));
+            }
+            profile.total_chars = match profile.total_chars.checked_add(count) {
+                Some(total_chars) => total_chars,
+                None => return Err(String::from("Profile entries total too many characters")),
+            };
+        }
+
+        /* Detect corruption by checking the counts add up to the recorded total */
+        if profile.total_chars != total_chars {
+            return Err(format!(
+                "Profile entries total {} characters, but header records {}",
+                profile.total_chars, total_chars
+            ));
+        }
+
+        Ok(profile)
+    }
+
+    /// Writes the profile to the given file
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        fs::write(filename, self.to_bytes())
+    }
+
+    /// Reads a profile which was previously written with save
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let bytes = match fs::read(filename) {
+            Ok(bytes) => bytes,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Adds count occurrences of c to the profile
+    fn add_count(&mut self, c: char, count: u64) {
+        *self.counts.entry(c).or_insert(0) += count;
+        self.total_chars += count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_character_percentages;
+
+    #[test]
+    fn profile_percentages_match_text() {
+        let profile = FrequencyProfile::from_text("aaaabbcc");
+        assert_eq!(profile.total_chars(), 8);
+        assert_eq!(profile.percentages(), get_character_percentages("aaaabbcc"));
+    }
+
+    #[test]
+    fn profile_to_bytes_and_back() {
+        let profile = FrequencyProfile::from_files(&["sample-text.txt"]).unwrap();
+
+        let result = FrequencyProfile::from_bytes(&profile.to_bytes());
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), profile);
+    }
+
+    #[test]
+    fn profile_merge_and_incremental_update() {
+        let mut merged = FrequencyProfile::from_text("hello ");
+        merged.merge(&FrequencyProfile::from_text("world"));
+
+        let mut incremental = FrequencyProfile::new();
+        incremental.add_text("hello ");
+        incremental.add_text("world");
+
+        assert_eq!(merged, FrequencyProfile::from_text("hello world"));
+        assert_eq!(incremental, merged);
+    }
+
+    #[test]
+    fn profile_invalid_bytes() {
+        let bytes = FrequencyProfile::from_text("abc").to_bytes();
+
+        /* Bad magic, unsupported version, truncated entries, and corrupted or overflowing counts */
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        let mut bad_version = bytes.clone();
+        bad_version[PROFILE_MAGIC.len()] = PROFILE_VERSION + 1;
+        let truncated = &bytes[..bytes.len() - 1];
+        let mut bad_count = bytes.clone();
+        let last = bad_count.len() - 1;
+        bad_count[last] ^= 1;
+        let mut overflowing = bytes.clone();
+        overflowing[last - 7..].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        for invalid in [
+            &bad_magic[..],
+            &bad_version,
+            truncated,
+            &bad_count,
+            &overflowing,
+            &[],
+        ] {
+            assert!(FrequencyProfile::from_bytes(invalid).is_err());
+        }
+    }
+}
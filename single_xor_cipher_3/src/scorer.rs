@@ -0,0 +1,68 @@
+//! This module defines the scorers which the crackers use to judge
+//! how plausible a candidate decoding is
+
+use std::{collections::HashMap, str};
+
+use crate::{get_chi_squared, get_normalized_character_percentages, Normalization};
+
+/// Judges how plausible a candidate plaintext is
+///
+/// Scores follow the chi-squared convention: the lower the score, the more
+/// plausible the plaintext, and scores are only compared within a single scorer
+pub trait PlaintextScorer: Sync {
+    /// Returns the score of the candidate plaintext, or None if
+    /// it cannot possibly be the plaintext
+    fn score(&self, plaintext: &[u8]) -> Option<f32>;
+
+    /// Returns the score of one column of a candidate plaintext, made up of
+    /// the bytes at offset, offset + stride, offset + 2 * stride and so on
+    ///
+    /// Crackers score a column at offset 0 with a stride of 1 using score instead,
+    /// since it is the whole plaintext. By default the column is scored as if its
+    /// bytes were contiguous, which suits scorers that only look at the distribution of bytes
+    fn score_column(&self, column: &[u8], _offset: usize, _stride: usize) -> Option<f32> {
+        self.score(column)
+    }
+}
+
+/// Scores plaintexts by the chi-squared metric between their character
+/// frequencies and the reference frequencies, rejecting invalid UTF-8 and
+/// plaintexts which normalization leaves with no characters to compare
+#[derive(Debug, Clone, Copy)]
+pub struct ChiSquaredScorer<'a> {
+    reference_percentages: &'a HashMap<char, f32>,
+    normalization: Normalization,
+}
+
+impl<'a> ChiSquaredScorer<'a> {
+    /// Returns a scorer which compares plaintexts to the reference percentages
+    pub fn new(reference_percentages: &'a HashMap<char, f32>) -> Self {
+        Self::with_normalization(reference_percentages, Normalization::default())
+    }
+
+    /// Returns a scorer which normalizes plaintexts before comparing them to
+    /// the reference percentages, which should be built with the same normalization
+    pub fn with_normalization(
+        reference_percentages: &'a HashMap<char, f32>,
+        normalization: Normalization,
+    ) -> Self {
+        Self {
+            reference_percentages,
+            normalization,
+        }
+    }
+}
+
+impl PlaintextScorer for ChiSquaredScorer<'_> {
+    fn score(&self, plaintext: &[u8]) -> Option<f32> {
+        let text = str::from_utf8(plaintext).ok()?;
+        let percentages = get_normalized_character_percentages(text, &self.normalization);
+
+        /* With nothing left to compare, the chi-squared is just the sum of the reference percentages */
+        if percentages.is_empty() {
+            return None;
+        }
+
+        Some(get_chi_squared(self.reference_percentages, percentages))
+    }
+}
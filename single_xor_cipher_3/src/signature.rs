@@ -0,0 +1,426 @@
+//! This module recognises binary plaintexts by their file signatures
+//!
+//! Character frequency analysis is meaningless when the plaintext is a
+//! binary file, so instead candidates are checked for the magic bytes of
+//! common formats, along with structural checks such as PNG chunk CRCs
+
+use crate::scorer::PlaintextScorer;
+
+/// The binary file formats which can be recognised
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Png,
+    Zip,
+    Gzip,
+    Pdf,
+    Elf,
+}
+
+impl FileType {
+    /// Every recognised file format
+    pub const ALL: [FileType; 5] = [
+        FileType::Png,
+        FileType::Zip,
+        FileType::Gzip,
+        FileType::Pdf,
+        FileType::Elf,
+    ];
+
+    /// Returns the fixed bytes at the start of every file of this format,
+    /// where None marks a byte which may take any value
+    pub fn header(&self) -> &'static [Option<u8>] {
+        const PNG: [Option<u8>; 16] = [
+            Some(0x89),
+            Some(b'P'),
+            Some(b'N'),
+            Some(b'G'),
+            Some(b'\r'),
+            Some(b'\n'),
+            Some(0x1a),
+            Some(b'\n'),
+            Some(0),
+            Some(0),
+            Some(0),
+            Some(13),
+            Some(b'I'),
+            Some(b'H'),
+            Some(b'D'),
+            Some(b'R'),
+        ];
+        const ZIP: [Option<u8>; 4] = [Some(b'P'), Some(b'K'), Some(3), Some(4)];
+        const GZIP: [Option<u8>; 3] = [Some(0x1f), Some(0x8b), Some(8)];
+        const PDF: [Option<u8>; 7] = [
+            Some(b'%'),
+            Some(b'P'),
+            Some(b'D'),
+            Some(b'F'),
+            Some(b'-'),
+            Some(b'1'),
+            Some(b'.'),
+        ];
+        const ELF: [Option<u8>; 16] = [
+            Some(0x7f),
+            Some(b'E'),
+            Some(b'L'),
+            Some(b'F'),
+            None,
+            None,
+            Some(1),
+            None,
+            None,
+            Some(0),
+            Some(0),
+            Some(0),
+            Some(0),
+            Some(0),
+            Some(0),
+            Some(0),
+        ];
+
+        match self {
+            FileType::Png => &PNG,
+            FileType::Zip => &ZIP,
+            FileType::Gzip => &GZIP,
+            FileType::Pdf => &PDF,
+            FileType::Elf => &ELF,
+        }
+    }
+
+    /// Returns how confident we are, between 0 and 1, that the bytes
+    /// are a file of this format, or None if its magic bytes are absent
+    pub fn confidence(&self, bytes: &[u8]) -> Option<f32> {
+        match self {
+            FileType::Png => png_confidence(bytes),
+            FileType::Zip => zip_confidence(bytes),
+            FileType::Gzip => gzip_confidence(bytes),
+            FileType::Pdf => pdf_confidence(bytes),
+            FileType::Elf => elf_confidence(bytes),
+        }
+    }
+}
+
+/// Returns the most likely file format of the bytes, along with the
+/// confidence in that format, or None if no file signature is present
+pub fn classify_file_signature(bytes: &[u8]) -> Option<(FileType, f32)> {
+    let mut best: Option<(FileType, f32)> = None;
+
+    for file_type in FileType::ALL {
+        if let Some(confidence) = file_type.confidence(bytes) {
+            if best.is_none() || confidence > best.unwrap().1 {
+                best = Some((file_type, confidence));
+            }
+        }
+    }
+
+    best
+}
+
+/// Scores plaintexts by how confidently they are recognised as a binary file
+///
+/// Columns are scored by how many of the fixed header bytes of the recognised
+/// formats they match, so only the key bytes which cover a header can be
+/// recovered when cracking repeating-key XOR with this scorer
+#[derive(Debug, Clone)]
+pub struct FileSignatureScorer {
+    file_types: Vec<FileType>,
+}
+
+impl FileSignatureScorer {
+    /// Returns a scorer which recognises every supported file format
+    pub fn new() -> Self {
+        Self::with_file_types(&FileType::ALL)
+    }
+
+    /// Returns a scorer which only recognises the given file formats
+    pub fn with_file_types(file_types: &[FileType]) -> Self {
+        Self {
+            file_types: file_types.to_vec(),
+        }
+    }
+}
+
+impl Default for FileSignatureScorer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlaintextScorer for FileSignatureScorer {
+    fn score(&self, plaintext: &[u8]) -> Option<f32> {
+        self.file_types
+            .iter()
+            .filter_map(|file_type| file_type.confidence(plaintext))
+            .reduce(f32::max)
+            .map(|confidence| 1.0 - confidence)
+    }
+
+    fn score_column(&self, column: &[u8], offset: usize, stride: usize) -> Option<f32> {
+        let mut best_score = 0.0;
+
+        for file_type in &self.file_types {
+            let mut score = 0.0;
+
+            /*
+             * Compare the column against each fixed header byte which falls into it,
+             * rewarding matches and penalising mismatches, so that formats whose
+             * headers give more evidence about this column win any ties
+             */
+            for (idx, &byte) in column.iter().enumerate() {
+                match file_type.header().get(offset + idx * stride) {
+                    Some(Some(expected)) if byte == *expected => score -= 1.0,
+                    Some(Some(_)) => score += 1.0,
+                    Some(None) => continue,
+                    None => break,
+                }
+            }
+
+            if score < best_score {
+                best_score = score;
+            }
+        }
+
+        /* Every key scores zero for columns which no header reaches, or which match no header */
+        Some(best_score)
+    }
+}
+
+/// Returns the confidence for a format whose magic bytes are present,
+/// given the results of its structural checks
+fn checks_to_confidence(checks: &[bool]) -> f32 {
+    let passed = checks.iter().filter(|&&check| check).count();
+    0.4 + 0.6 * passed as f32 / checks.len() as f32
+}
+
+/// Returns true if the bytes start with the format's fixed header bytes
+fn matches_header(bytes: &[u8], header: &[Option<u8>]) -> bool {
+    bytes.len() >= header.len()
+        && header
+            .iter()
+            .zip(bytes)
+            .all(|(expected, byte)| expected.is_none_or(|expected| expected == *byte))
+}
+
+/// Checks the PNG signature, then walks the chunks verifying their CRCs
+fn png_confidence(bytes: &[u8]) -> Option<f32> {
+    if !matches_header(bytes, FileType::Png.header()) {
+        return None;
+    }
+
+    let mut position = 8;
+    let mut chunks = 0;
+    let mut valid_chunks = 0;
+
+    /* Each chunk is a length, a type, its data and a CRC of the type and data */
+    while position + 12 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[position..position + 4].try_into().unwrap()) as usize;
+        let crc_start = position + 8 + length;
+        if crc_start + 4 > bytes.len() {
+            break;
+        }
+
+        let expected_crc = u32::from_be_bytes(bytes[crc_start..crc_start + 4].try_into().unwrap());
+        chunks += 1;
+        if crc32(&bytes[position + 4..crc_start]) == expected_crc {
+            valid_chunks += 1;
+        }
+
+        position = crc_start + 4;
+    }
+
+    if chunks == 0 {
+        return Some(checks_to_confidence(&[false]));
+    }
+
+    Some(0.4 + 0.6 * valid_chunks as f32 / chunks as f32)
+}
+
+/// Checks the ZIP local file header, or an empty archive's end of central directory
+fn zip_confidence(bytes: &[u8]) -> Option<f32> {
+    if bytes.len() >= 22 && bytes.starts_with(b"PK\x05\x06") {
+        return Some(checks_to_confidence(&[bytes.len() == 22]));
+    }
+
+    if !matches_header(bytes, FileType::Zip.header()) {
+        return None;
+    }
+
+    if bytes.len() < 30 {
+        return Some(checks_to_confidence(&[false, false, false]));
+    }
+
+    let version_needed = u16::from_le_bytes([bytes[4], bytes[5]]);
+    let compression_method = u16::from_le_bytes([bytes[8], bytes[9]]);
+    let name_length = u16::from_le_bytes([bytes[26], bytes[27]]) as usize;
+    let name = bytes.get(30..30 + name_length);
+
+    Some(checks_to_confidence(&[
+        version_needed <= 63,
+        matches!(
+            compression_method,
+            0 | 1 | 6 | 8 | 9 | 12 | 14 | 93 | 95 | 98 | 99
+        ),
+        name_length > 0
+            && name.is_some_and(|name| name.iter().all(|c| c.is_ascii_graphic() || *c == b' ')),
+    ]))
+}
+
+/// Checks the gzip member header's flags, extra flags and operating system
+fn gzip_confidence(bytes: &[u8]) -> Option<f32> {
+    if !matches_header(bytes, FileType::Gzip.header()) {
+        return None;
+    }
+
+    if bytes.len() < 10 {
+        return Some(checks_to_confidence(&[false, false, false]));
+    }
+
+    Some(checks_to_confidence(&[
+        bytes[3] & 0xe0 == 0,
+        matches!(bytes[8], 0 | 2 | 4),
+        bytes[9] <= 13 || bytes[9] == 255,
+    ]))
+}
+
+/// Checks the PDF version number and the end of file marker
+fn pdf_confidence(bytes: &[u8]) -> Option<f32> {
+    if !matches_header(bytes, FileType::Pdf.header()) {
+        return None;
+    }
+
+    let tail = &bytes[bytes.len().saturating_sub(1024)..];
+
+    Some(checks_to_confidence(&[
+        bytes.get(7).is_some_and(u8::is_ascii_digit),
+        tail.windows(5).any(|window| window == b"%%EOF"),
+    ]))
+}
+
+/// Checks the ELF identification bytes and object file type
+fn elf_confidence(bytes: &[u8]) -> Option<f32> {
+    if !bytes.starts_with(b"\x7fELF") {
+        return None;
+    }
+
+    if bytes.len() < 18 {
+        return Some(checks_to_confidence(&[false, false, false, false]));
+    }
+
+    /* The object file type is stored in the byte order given by the data encoding */
+    let object_type = match bytes[5] {
+        2 => u16::from_be_bytes([bytes[16], bytes[17]]),
+        _ => u16::from_le_bytes([bytes[16], bytes[17]]),
+    };
+
+    Some(checks_to_confidence(&[
+        matches!(bytes[4], 1 | 2),
+        matches!(bytes[5], 1 | 2),
+        matches_header(bytes, FileType::Elf.header()),
+        (1..=4).contains(&object_type),
+    ]))
+}
+
+/// Calculates the CRC-32 checksum used by PNG, gzip and ZIP
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffffffff;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a PNG file containing a header chunk and an end chunk
+    fn build_png() -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+        let chunks: [(&[u8], &[u8]); 2] = [
+            (b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            (b"IEND", &[]),
+        ];
+        for (chunk_type, data) in chunks {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let crc_start = png.len();
+            png.extend_from_slice(chunk_type);
+            png.extend_from_slice(data);
+            let crc = crc32(&png[crc_start..]);
+            png.extend_from_slice(&crc.to_be_bytes());
+        }
+
+        png
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn classify_signatures() {
+        let mut corrupted_png = build_png();
+        let last = corrupted_png.len() - 1;
+        corrupted_png[last] ^= 1;
+
+        let test_data: [(Vec<u8>, FileType, f32); 6] = [
+            (build_png(), FileType::Png, 1.0),
+            (corrupted_png, FileType::Png, 0.7),
+            (
+                b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03".to_vec(),
+                FileType::Gzip,
+                1.0,
+            ),
+            (b"%PDF-1.7\n1 0 obj\n%%EOF\n".to_vec(), FileType::Pdf, 1.0),
+            (
+                b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\x00".to_vec(),
+                FileType::Elf,
+                1.0,
+            ),
+            (
+                b"PK\x05\x06\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"
+                    .to_vec(),
+                FileType::Zip,
+                1.0,
+            ),
+        ];
+
+        for (bytes, file_type, confidence) in test_data {
+            let result = classify_file_signature(&bytes);
+            assert!(result.is_some());
+
+            let (classified_type, classified_confidence) = result.unwrap();
+            assert_eq!(classified_type, file_type);
+            assert!((classified_confidence - confidence).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn classify_text_is_none() {
+        assert_eq!(
+            classify_file_signature(b"Cooking MC's like a pound of bacon"),
+            None
+        );
+        assert_eq!(classify_file_signature(b""), None);
+    }
+
+    #[test]
+    fn score_columns_against_header() {
+        let scorer = FileSignatureScorer::with_file_types(&[FileType::Png]);
+        let png = build_png();
+
+        /* The first column of a 4 byte key holds the PNG bytes at offsets 0, 4, 8 and 12 */
+        let column: Vec<u8> = png.iter().step_by(4).copied().collect();
+        assert_eq!(scorer.score_column(&column, 0, 4), Some(-4.0));
+
+        let wrong_column: Vec<u8> = column.iter().map(|byte| byte ^ 1).collect();
+        assert_eq!(scorer.score_column(&wrong_column, 0, 4), Some(0.0));
+    }
+}
@@ -0,0 +1,526 @@
+//! This module solves monoalphabetic substitution ciphers, where each letter
+//! of the alphabet is swapped for another one throughout the message
+//!
+//! There are 26! keys, far too many to try, so the key is searched for instead.
+//! The search starts by pairing the most common letters of the message with the
+//! most common letters of the language, then keeps swapping pairs of letters in
+//! the key, keeping the swaps which make the plaintext more likely under an n-gram
+//! model. Each restart begins from a different key, as the search can get stuck
+//! on a key which no single swap improves
+
+use std::fmt;
+
+use crypto_utilities::parallel::parallel_map;
+
+use crate::{ngram::NgramModel, scorer::PlaintextScorer};
+
+/// Number of letters in the alphabet
+const ALPHABET_LEN: usize = 26;
+
+/// Number of random swaps made to the starting key of every restart but the first
+const RESTART_SWAPS: usize = 8;
+
+/// A substitution key, giving the plaintext letter for each ciphertext letter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubstitutionKey {
+    /* plain_letters[c] is the plaintext letter for ciphertext letter c, both numbered from 0 for A */
+    plain_letters: [u8; ALPHABET_LEN],
+}
+
+impl SubstitutionKey {
+    /// Returns the key which leaves every letter as it is
+    pub fn identity() -> Self {
+        let mut plain_letters = [0; ALPHABET_LEN];
+        for (letter, plain_letter) in plain_letters.iter_mut().enumerate() {
+            *plain_letter = letter as u8;
+        }
+
+        Self { plain_letters }
+    }
+
+    /// Returns the key from its ciphertext alphabet, the letters which
+    /// A to Z encrypt to, such as "QWERTYUIOPASDFGHJKLZXCVBNM"
+    pub fn from_cipher_alphabet(alphabet: &str) -> Result<Self, String> {
+        if alphabet.len() != ALPHABET_LEN || !alphabet.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Err(String::from(
+                "The cipher alphabet must be made of the 26 letters A to Z",
+            ));
+        }
+
+        let mut plain_letters = [None; ALPHABET_LEN];
+        for (plain_letter, cipher_letter) in alphabet.bytes().enumerate() {
+            let cipher_letter = cipher_letter.to_ascii_uppercase() - b'A';
+            if plain_letters[cipher_letter as usize]
+                .replace(plain_letter as u8)
+                .is_some()
+            {
+                return Err(format!(
+                    "The cipher alphabet has the letter {} more than once",
+                    (cipher_letter + b'A') as char
+                ));
+            }
+        }
+
+        /* 26 distinct letters must cover the whole alphabet */
+        Ok(Self {
+            plain_letters: plain_letters.map(Option::unwrap),
+        })
+    }
+
+    /// Returns the ciphertext alphabet, the letters which A to Z encrypt to
+    pub fn cipher_alphabet(&self) -> String {
+        let mut alphabet = [0; ALPHABET_LEN];
+        for (cipher_letter, &plain_letter) in self.plain_letters.iter().enumerate() {
+            alphabet[plain_letter as usize] = cipher_letter as u8 + b'A';
+        }
+
+        alphabet.iter().map(|&letter| letter as char).collect()
+    }
+
+    /// Returns the plaintext letter for a ciphertext letter, where letters
+    /// are numbered from 0 for A to 25 for Z, and larger numbers wrap around
+    pub fn decrypt_letter(&self, letter: u8) -> u8 {
+        self.plain_letters[(letter % ALPHABET_LEN as u8) as usize]
+    }
+
+    /// Returns the ciphertext letter for a plaintext letter, where letters
+    /// are numbered from 0 for A to 25 for Z, and larger numbers wrap around
+    pub fn encrypt_letter(&self, letter: u8) -> u8 {
+        let letter = letter % ALPHABET_LEN as u8;
+
+        /* The key is a permutation, so every letter is some ciphertext letter's plaintext */
+        self.plain_letters
+            .iter()
+            .position(|&plain_letter| plain_letter == letter)
+            .unwrap() as u8
+    }
+}
+
+impl fmt::Display for SubstitutionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.cipher_alphabet())
+    }
+}
+
+/// How the key is searched for when solving a substitution cipher
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubstitutionOptions {
+    /// Number of searches made, each from a different starting key
+    pub restarts: usize,
+    /// Number of swaps tried in each search
+    pub iterations: usize,
+    /// Starting temperature for simulated annealing, in nats of the whole
+    /// plaintext's log likelihood, falling to zero by the last iteration.
+    /// At zero, only swaps which improve the plaintext are kept
+    pub start_temperature: f64,
+    /// Seed of the random swaps, so that the same seed gives the same solution
+    pub seed: u64,
+}
+
+impl Default for SubstitutionOptions {
+    fn default() -> Self {
+        Self {
+            restarts: 8,
+            iterations: 4000,
+            start_temperature: 0.0,
+            seed: 0x5eed,
+        }
+    }
+}
+
+/// A key found for a substitution cipher, and the plaintext it decrypts to
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubstitutionSolution {
+    /// The key found by the search. Letters which are not in the message
+    /// cannot be told apart, so their part of the key is a guess
+    pub key: SubstitutionKey,
+    /// The message obtained by decrypting with the key
+    pub plaintext: String,
+    /// The n-gram model's judgement of the plaintext (lower is better)
+    pub score: f32,
+}
+
+/// Returns the message encrypted with the key, keeping the case
+/// of each letter and passing every other character through
+pub fn substitution_encrypt(msg: &str, key: &SubstitutionKey) -> String {
+    apply_key(msg, |letter| key.encrypt_letter(letter))
+}
+
+/// Returns the message decrypted with the key, keeping the case
+/// of each letter and passing every other character through
+pub fn substitution_decrypt(encrypted_msg: &str, key: &SubstitutionKey) -> String {
+    apply_key(encrypted_msg, |letter| key.decrypt_letter(letter))
+}
+
+/// Solves a substitution cipher by hill climbing, or simulated annealing,
+/// from a key which matches the letter frequencies of the message to those
+/// of the model, judging each key by how likely the model finds its plaintext
+///
+/// Each fixed mapping pairs a ciphertext letter with the plaintext letter it is
+/// known to stand for, in either case, and is kept by every key tried
+///
+/// On success, it will return the best key found over all of the restarts
+pub fn solve_substitution(
+    encrypted_msg: &str,
+    model: &NgramModel,
+    fixed: &[(char, char)],
+    options: &SubstitutionOptions,
+) -> Result<SubstitutionSolution, String> {
+    solve_substitution_parallel(encrypted_msg, model, fixed, options, 1)
+}
+
+/// Returns the same solution as solve_substitution,
+/// running the restarts on up to no_of_threads threads
+///
+/// The result does not depend on the number of threads
+pub fn solve_substitution_parallel(
+    encrypted_msg: &str,
+    model: &NgramModel,
+    fixed: &[(char, char)],
+    options: &SubstitutionOptions,
+    no_of_threads: usize,
+) -> Result<SubstitutionSolution, String> {
+    let fixed = get_fixed_letters(fixed)?;
+
+    let mut letter_counts = [0usize; ALPHABET_LEN];
+    for byte in encrypted_msg.bytes().filter(u8::is_ascii_alphabetic) {
+        letter_counts[(byte.to_ascii_uppercase() - b'A') as usize] += 1;
+    }
+    if letter_counts.iter().all(|&count| count == 0) {
+        return Err(String::from("The message has no letters to decrypt"));
+    }
+
+    let start_key = get_frequency_aligned_key(&letter_counts, model, &fixed);
+
+    /* Only the free letters are swapped, and one of each pair must be in the message */
+    let free_letters: Vec<u8> = (0..ALPHABET_LEN as u8)
+        .filter(|&letter| fixed[letter as usize].is_none())
+        .collect();
+    let present_free_letters: Vec<u8> = free_letters
+        .iter()
+        .copied()
+        .filter(|&letter| letter_counts[letter as usize] > 0)
+        .collect();
+
+    let restarts: Vec<usize> = (0..options.restarts.max(1)).collect();
+    let attempts = parallel_map(&restarts, no_of_threads, |&restart| {
+        let mut rng = XorShift::new(options.seed, restart as u64);
+
+        let mut key = start_key;
+        if restart > 0 && free_letters.len() > 1 {
+            for _ in 0..RESTART_SWAPS {
+                let a = free_letters[rng.below(free_letters.len())];
+                let b = free_letters[rng.below(free_letters.len())];
+                key.plain_letters.swap(a as usize, b as usize);
+            }
+        }
+
+        climb(
+            encrypted_msg,
+            key,
+            model,
+            &free_letters,
+            &present_free_letters,
+            options,
+            &mut rng,
+        )
+    });
+
+    /* Keep the earliest restart among equal scores */
+    attempts
+        .into_iter()
+        .flatten()
+        .reduce(|best, solution| {
+            if solution.score < best.score {
+                solution
+            } else {
+                best
+            }
+        })
+        .ok_or_else(|| String::from("Could not decrypt the given message."))
+}
+
+/// Searches for a better key by swapping pairs of free letters, and returns
+/// the best key seen, or None if the model could not score the plaintext
+fn climb(
+    encrypted_msg: &str,
+    start_key: SubstitutionKey,
+    model: &NgramModel,
+    free_letters: &[u8],
+    present_free_letters: &[u8],
+    options: &SubstitutionOptions,
+    rng: &mut XorShift,
+) -> Option<SubstitutionSolution> {
+    let plaintext = substitution_decrypt(encrypted_msg, &start_key);
+    let score = model.score(plaintext.as_bytes())?;
+    let mut current = SubstitutionSolution {
+        key: start_key,
+        plaintext,
+        score,
+    };
+    let mut best = current.clone();
+
+    /* Nothing can be swapped if every letter in the message is fixed */
+    if present_free_letters.is_empty() || free_letters.len() < 2 {
+        return Some(best);
+    }
+
+    /* Scores are averaged per byte, so scale differences back up to the whole plaintext */
+    let msg_len = encrypted_msg.len() as f64;
+
+    for iteration in 0..options.iterations {
+        let a = present_free_letters[rng.below(present_free_letters.len())];
+        let b = free_letters[rng.below(free_letters.len())];
+        if a == b {
+            continue;
+        }
+
+        let mut key = current.key;
+        key.plain_letters.swap(a as usize, b as usize);
+        let plaintext = substitution_decrypt(encrypted_msg, &key);
+        let score = match model.score(plaintext.as_bytes()) {
+            Some(score) => score,
+            None => continue,
+        };
+
+        let worsening = (score as f64 - current.score as f64) * msg_len;
+        let temperature =
+            options.start_temperature * (1.0 - iteration as f64 / options.iterations as f64);
+        let accepted = worsening <= 0.0
+            || (temperature > 0.0 && rng.next_f64() < (-worsening / temperature).exp());
+        if !accepted {
+            continue;
+        }
+
+        current = SubstitutionSolution {
+            key,
+            plaintext,
+            score,
+        };
+        if current.score < best.score {
+            best = current.clone();
+        }
+    }
+
+    Some(best)
+}
+
+/// Returns the plaintext letter fixed for each ciphertext letter, or an error
+/// if a mapping is not between two letters or contradicts another one
+fn get_fixed_letters(fixed: &[(char, char)]) -> Result<[Option<u8>; ALPHABET_LEN], String> {
+    let mut plain_for_cipher = [None; ALPHABET_LEN];
+    let mut cipher_for_plain = [None; ALPHABET_LEN];
+
+    for &(cipher_char, plain_char) in fixed {
+        if !cipher_char.is_ascii_alphabetic() || !plain_char.is_ascii_alphabetic() {
+            return Err(format!(
+                "The fixed mapping {} -> {} must be between two letters",
+                cipher_char, plain_char
+            ));
+        }
+
+        let cipher_letter = cipher_char.to_ascii_uppercase() as u8 - b'A';
+        let plain_letter = plain_char.to_ascii_uppercase() as u8 - b'A';
+        let previous_plain = plain_for_cipher[cipher_letter as usize].replace(plain_letter);
+        let previous_cipher = cipher_for_plain[plain_letter as usize].replace(cipher_letter);
+
+        if previous_plain.is_some_and(|previous| previous != plain_letter)
+            || previous_cipher.is_some_and(|previous| previous != cipher_letter)
+        {
+            return Err(format!(
+                "The fixed mapping {} -> {} contradicts another fixed mapping",
+                cipher_char, plain_char
+            ));
+        }
+    }
+
+    Ok(plain_for_cipher)
+}
+
+/// Returns the key which pairs the free ciphertext letters, most common first,
+/// with the free plaintext letters the model finds most likely, keeping the fixed letters
+fn get_frequency_aligned_key(
+    letter_counts: &[usize; ALPHABET_LEN],
+    model: &NgramModel,
+    fixed: &[Option<u8>; ALPHABET_LEN],
+) -> SubstitutionKey {
+    /* A letter's probability is that of either of its cases, with no context */
+    let letter_probability = |letter: u8| {
+        model.log_probability(&[], letter + b'A').exp()
+            + model.log_probability(&[], letter + b'a').exp()
+    };
+
+    /* The sorts are stable, so ties keep alphabetical order */
+    let mut cipher_letters: Vec<u8> = (0..ALPHABET_LEN as u8)
+        .filter(|&letter| fixed[letter as usize].is_none())
+        .collect();
+    cipher_letters.sort_by(|&a, &b| letter_counts[b as usize].cmp(&letter_counts[a as usize]));
+
+    let mut plain_letters: Vec<u8> = (0..ALPHABET_LEN as u8)
+        .filter(|&letter| !fixed.contains(&Some(letter)))
+        .collect();
+    plain_letters.sort_by(|&a, &b| letter_probability(b).total_cmp(&letter_probability(a)));
+
+    let mut key = SubstitutionKey::identity();
+    for (cipher_letter, &plain_letter) in fixed.iter().enumerate() {
+        if let Some(plain_letter) = plain_letter {
+            key.plain_letters[cipher_letter] = plain_letter;
+        }
+    }
+    for (cipher_letter, plain_letter) in cipher_letters.into_iter().zip(plain_letters) {
+        key.plain_letters[cipher_letter as usize] = plain_letter;
+    }
+
+    key
+}
+
+/// Replaces each letter of the message, keeping its case,
+/// and passes every other character through
+fn apply_key(msg: &str, substitute: impl Fn(u8) -> u8) -> String {
+    msg.chars()
+        .map(|c| {
+            if !c.is_ascii_alphabetic() {
+                return c;
+            }
+
+            let base = if c.is_ascii_uppercase() { b'A' } else { b'a' };
+            (substitute(c as u8 - base) + base) as char
+        })
+        .collect()
+}
+
+/// A small xorshift random number generator, which is plenty for picking swaps
+/// and gives the same sequence on every platform
+struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    /// Returns a generator for one stream of the seed, so each restart gets its own
+    fn new(seed: u64, stream: u64) -> Self {
+        /* Mix the seed and stream with splitmix64, which never gives the zero state */
+        let mut z = seed
+            .wrapping_add(stream.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+
+        Self { state: z.max(1) }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a number from 0 up to, but not including, n
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns a number from 0 up to, but not including, 1
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSG: &str =
+        "Four score and seven years ago our fathers brought forth on this continent, \
+        a new nation, conceived in Liberty, and dedicated to the proposition that all men are \
+        created equal. Now we are engaged in a great civil war, testing whether that nation, or \
+        any nation so conceived and so dedicated, can long endure. We are met on a great \
+        battle-field of that war.";
+
+    const CIPHER_ALPHABET: &str = "QWERTYUIOPASDFGHJKLZXCVBNM";
+
+    #[test]
+    fn substitution_keys() {
+        let key = SubstitutionKey::from_cipher_alphabet(CIPHER_ALPHABET).unwrap();
+        assert_eq!(key.cipher_alphabet(), CIPHER_ALPHABET);
+        assert_eq!(key.to_string(), CIPHER_ALPHABET);
+
+        let encrypted = substitution_encrypt("Attack at dawn!", &key);
+        assert_eq!(encrypted, "Qzzqea qz rqvf!");
+        assert_eq!(substitution_decrypt(&encrypted, &key), "Attack at dawn!");
+
+        let identity = SubstitutionKey::identity();
+        assert_eq!(substitution_encrypt(MSG, &identity), MSG);
+
+        /* Letter numbers past Z wrap around rather than panicking */
+        assert_eq!(key.encrypt_letter(26), key.encrypt_letter(0));
+        assert_eq!(key.decrypt_letter(255), key.decrypt_letter(255 % 26));
+
+        assert!(SubstitutionKey::from_cipher_alphabet("QWERTY").is_err());
+        assert!(SubstitutionKey::from_cipher_alphabet("QQERTYUIOPASDFGHJKLZXCVBNM").is_err());
+        assert!(SubstitutionKey::from_cipher_alphabet("QWERTYUIOPASDFGHJKLZXCVBN1").is_err());
+    }
+
+    #[test]
+    fn solve_with_hill_climbing() {
+        let model = NgramModel::from_files(&["reference-text.txt"], 3).unwrap();
+        let key = SubstitutionKey::from_cipher_alphabet(CIPHER_ALPHABET).unwrap();
+        let encrypted = substitution_encrypt(MSG, &key);
+
+        let options = SubstitutionOptions {
+            restarts: 6,
+            iterations: 3000,
+            ..Default::default()
+        };
+        let result = solve_substitution(&encrypted, &model, &[], &options);
+        assert!(result.is_ok());
+
+        let solution = result.unwrap();
+        assert_eq!(solution.plaintext, MSG);
+
+        /* Every letter in the message is recovered, whatever the key says about the rest */
+        assert!(MSG
+            .bytes()
+            .filter(u8::is_ascii_alphabetic)
+            .map(|byte| byte.to_ascii_uppercase() - b'A')
+            .all(|letter| solution.key.encrypt_letter(letter) == key.encrypt_letter(letter)));
+
+        /* Every thread count should give the same solution */
+        let parallel = solve_substitution_parallel(&encrypted, &model, &[], &options, 3);
+        assert_eq!(parallel, Ok(solution));
+    }
+
+    #[test]
+    fn fixed_mappings_are_kept() {
+        let model = NgramModel::from_files(&["reference-text.txt"], 3).unwrap();
+        let key = SubstitutionKey::from_cipher_alphabet(CIPHER_ALPHABET).unwrap();
+        let encrypted = substitution_encrypt(MSG, &key);
+
+        /* A wrong fixed mapping is kept, even though it garbles the plaintext */
+        let options = SubstitutionOptions {
+            restarts: 2,
+            iterations: 1000,
+            start_temperature: 5.0,
+            ..Default::default()
+        };
+        let solution =
+            solve_substitution(&encrypted, &model, &[('z', 'X'), ('Q', 'A')], &options).unwrap();
+        assert_eq!(solution.key.decrypt_letter(b'Z' - b'A'), b'X' - b'A');
+        assert_eq!(solution.key.decrypt_letter(b'Q' - b'A'), b'A' - b'A');
+
+        /* No other ciphertext letter may also decrypt to X */
+        assert!((0..ALPHABET_LEN as u8)
+            .filter(|&letter| letter != b'Z' - b'A')
+            .all(|letter| solution.key.decrypt_letter(letter) != b'X' - b'A'));
+
+        assert!(
+            solve_substitution(&encrypted, &model, &[('Z', 'T'), ('Z', 'E')], &options).is_err()
+        );
+        assert!(
+            solve_substitution(&encrypted, &model, &[('Z', 'T'), ('Q', 'T')], &options).is_err()
+        );
+        assert!(solve_substitution(&encrypted, &model, &[('Z', '1')], &options).is_err());
+        assert!(solve_substitution("123 !?", &model, &[], &options).is_err());
+    }
+}